@@ -0,0 +1,245 @@
+//! Due dates and the agenda grouping shared by the TUI and `note agenda`.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use std::fmt;
+
+use crate::NoteFormat;
+
+/// A due date, optionally with a time. Notes without a time are due at the
+/// end of the day, so they only become overdue once the date has passed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Due {
+    pub date: NaiveDate,
+    pub time: Option<NaiveTime>,
+}
+
+impl Due {
+    /// Accepts `YYYY-MM-DD`, `YYYY-MM-DD HH:MM` and `YYYY-MM-DDTHH:MM`.
+    pub fn parse(s: &str) -> Option<Due> {
+        let s = s.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Some(Due { date, time: None });
+        }
+        ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"]
+            .iter()
+            .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+            .map(|dt| Due {
+                date: dt.date(),
+                time: Some(dt.time()),
+            })
+    }
+
+    /// Form used in `note.meta`, without spaces so it needs no escaping.
+    pub fn to_meta(self) -> String {
+        match self.time {
+            Some(time) => format!("{}T{}", self.date, time.format("%H:%M")),
+            None => self.date.to_string(),
+        }
+    }
+
+    pub fn is_overdue(self, now: NaiveDateTime) -> bool {
+        match self.time {
+            Some(time) => self.date.and_time(time) < now,
+            None => self.date < now.date(),
+        }
+    }
+
+    pub fn bucket(self, now: NaiveDateTime) -> Bucket {
+        let today = now.date();
+        let end_of_week = today + Duration::days(6 - today.weekday().num_days_from_monday() as i64);
+        if self.is_overdue(now) {
+            Bucket::Overdue
+        } else if self.date == today {
+            Bucket::Today
+        } else if self.date <= end_of_week {
+            Bucket::ThisWeek
+        } else {
+            Bucket::Later
+        }
+    }
+}
+
+impl fmt::Display for Due {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.time {
+            Some(time) => write!(f, "{} {}", self.date, time.format("%H:%M")),
+            None => write!(f, "{}", self.date),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bucket {
+    Overdue,
+    Today,
+    ThisWeek,
+    Later,
+}
+
+impl Bucket {
+    pub const ALL: [Bucket; 4] = [
+        Bucket::Overdue,
+        Bucket::Today,
+        Bucket::ThisWeek,
+        Bucket::Later,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Bucket::Overdue => "Overdue",
+            Bucket::Today => "Today",
+            Bucket::ThisWeek => "This week",
+            Bucket::Later => "Later",
+        }
+    }
+}
+
+/// Open, unarchived notes with a due date grouped by bucket, each group
+/// sorted by due date.
+/// The `usize` is the note's index in `notes`. Empty groups are kept so
+/// callers can decide whether to show them.
+pub fn group(notes: &[NoteFormat], now: NaiveDateTime) -> Vec<(Bucket, Vec<(usize, &NoteFormat)>)> {
+    Bucket::ALL
+        .iter()
+        .map(|&bucket| {
+            let mut entries: Vec<(usize, &NoteFormat)> = notes
+                .iter()
+                .enumerate()
+                .filter(|(_, note)| note.done.is_none() && !note.archived)
+                .filter(|(_, note)| note.due.is_some_and(|due| due.bucket(now) == bucket))
+                .collect();
+            entries.sort_by_key(|(_, note)| note.due);
+            (bucket, entries)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn due(s: &str) -> Due {
+        Due::parse(s).unwrap_or_else(|| panic!("failed to parse {:?}", s))
+    }
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn note(text: &str, due_on: &str) -> NoteFormat {
+        NoteFormat {
+            text: text.to_string(),
+            due: Some(due(due_on)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dues_parse_with_and_without_a_time() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+        let time = NaiveTime::from_hms_opt(9, 30, 0);
+        assert_eq!(due("2024-05-15"), Due { date, time: None });
+        assert_eq!(due("2024-05-15 09:30"), Due { date, time });
+        assert_eq!(due(" 2024-05-15T09:30 "), Due { date, time });
+        assert_eq!(due("2024-05-15 09:30").to_meta(), "2024-05-15T09:30");
+        assert_eq!(due("2024-05-15T09:30").to_string(), "2024-05-15 09:30");
+        assert_eq!(Due::parse("2024-02-30"), None);
+        assert_eq!(Due::parse("15/05/2024"), None);
+    }
+
+    #[test]
+    fn timed_dues_are_overdue_once_their_minute_has_passed() {
+        let now = at("2024-05-15 09:30:00");
+        assert!(due("2024-05-15 09:29").is_overdue(now));
+        // due at this very moment is not late yet
+        assert!(!due("2024-05-15 09:30").is_overdue(now));
+        assert!(due("2024-05-15 09:30").is_overdue(at("2024-05-15 09:30:01")));
+    }
+
+    #[test]
+    fn buckets_at_midnight() {
+        let midnight = at("2024-05-15 00:00:00");
+        assert_eq!(due("2024-05-14 23:59").bucket(midnight), Bucket::Overdue);
+        assert_eq!(due("2024-05-14").bucket(midnight), Bucket::Overdue);
+        assert_eq!(due("2024-05-15 00:00").bucket(midnight), Bucket::Today);
+        assert_eq!(due("2024-05-15").bucket(midnight), Bucket::Today);
+
+        let last_second = at("2024-05-15 23:59:59");
+        assert_eq!(due("2024-05-15 23:59").bucket(last_second), Bucket::Overdue);
+        assert_eq!(due("2024-05-15").bucket(last_second), Bucket::Today);
+        assert_eq!(
+            due("2024-05-16 00:00").bucket(last_second),
+            Bucket::ThisWeek
+        );
+    }
+
+    #[test]
+    fn date_only_dues_last_the_whole_day() {
+        let day = due("2024-05-15");
+        assert!(!day.is_overdue(at("2024-05-15 00:00:00")));
+        assert!(!day.is_overdue(at("2024-05-15 23:59:59")));
+        assert!(day.is_overdue(at("2024-05-16 00:00:00")));
+    }
+
+    #[test]
+    fn the_week_ends_on_sunday() {
+        // a Wednesday
+        let now = at("2024-05-15 12:00:00");
+        assert_eq!(due("2024-05-19").bucket(now), Bucket::ThisWeek);
+        assert_eq!(due("2024-05-19 23:59").bucket(now), Bucket::ThisWeek);
+        assert_eq!(due("2024-05-20").bucket(now), Bucket::Later);
+        // on Sunday, Monday is already later
+        let sunday = at("2024-05-19 12:00:00");
+        assert_eq!(due("2024-05-19").bucket(sunday), Bucket::Today);
+        assert_eq!(due("2024-05-20 00:00").bucket(sunday), Bucket::Later);
+    }
+
+    #[test]
+    fn group_sorts_open_notes_into_buckets() {
+        let now = at("2024-05-15 12:00:00");
+        let notes = [
+            note("later", "2024-06-01"),
+            note("overdue timed", "2024-05-15 08:00"),
+            note("this week", "2024-05-17"),
+            note("today", "2024-05-15 18:00"),
+            note("overdue", "2024-05-10"),
+            NoteFormat {
+                done: Some(at("2024-05-14 10:00:00")),
+                ..note("done", "2024-05-10")
+            },
+            NoteFormat {
+                archived: true,
+                ..note("archived", "2024-05-15")
+            },
+            NoteFormat {
+                text: "no due".to_string(),
+                ..Default::default()
+            },
+            note("today, all day", "2024-05-15"),
+            note("sooner this week", "2024-05-16"),
+        ];
+        let groups: Vec<(Bucket, Vec<usize>)> = group(&notes, now)
+            .into_iter()
+            .map(|(bucket, entries)| (bucket, entries.iter().map(|&(i, _)| i).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (Bucket::Overdue, vec![4, 1]),
+                (Bucket::Today, vec![8, 3]),
+                (Bucket::ThisWeek, vec![9, 2]),
+                (Bucket::Later, vec![0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_keeps_empty_buckets() {
+        let groups = group(&[], at("2024-05-15 12:00:00"));
+        assert_eq!(
+            groups.iter().map(|(bucket, _)| *bucket).collect::<Vec<_>>(),
+            Bucket::ALL
+        );
+        assert!(groups.iter().all(|(_, entries)| entries.is_empty()));
+    }
+}
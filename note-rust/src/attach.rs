@@ -0,0 +1,27 @@
+//! File attachments: paths to logs, configs or screenshots kept with a note.
+//!
+//! Only the path is stored, never a copy, so an attachment can go missing
+//! when the file is moved or deleted.
+
+use std::path::{Path, PathBuf};
+
+/// `path` as stored: `~/` expanded and made absolute against the current
+/// directory, so the reference keeps working from anywhere.
+pub fn absolute(path: &str) -> PathBuf {
+    let path = path.trim();
+    let expanded = match (path.strip_prefix("~/"), std::env::var("HOME")) {
+        (Some(rest), Ok(home)) => Path::new(&home).join(rest),
+        _ => PathBuf::from(path),
+    };
+    if expanded.is_absolute() {
+        expanded
+    } else {
+        std::env::current_dir()
+            .map(|dir| dir.join(&expanded))
+            .unwrap_or(expanded)
+    }
+}
+
+pub fn is_missing(path: &str) -> bool {
+    !Path::new(path).exists()
+}
@@ -0,0 +1,455 @@
+//! Non-interactive commands, run as `note-rust <command>`.
+//!
+//! The zsh `note` script forwards the commands it doesn't implement itself
+//! here. Note numbers are 0-based to match `note list`.
+
+use chrono::{Duration, Local};
+use color_eyre::eyre::{eyre, Result};
+use serde_json::{json, Map, Value};
+
+use crate::fields::FieldFilter;
+use crate::filter::Filter;
+use crate::label::Label;
+use crate::meta;
+use crate::priority::Priority;
+use crate::tree;
+use crate::undo::Change;
+use crate::view::{self, Panel, SortMode, ViewOptions};
+use crate::{
+    add_note, agenda, auto_archive, auto_archive_days, file_path, load_note_list, save_meta,
+    save_notes, NoteFormat,
+};
+use crate::{attach, journal, template, timelog};
+
+pub fn run(args: &[String]) -> Result<()> {
+    match args[0].as_str() {
+        "add" => add_command(&args[1..]),
+        "agenda" => agenda_command(),
+        "attach" => attach_command(&args[1..]),
+        "today" => today_command(&args[1..]),
+        "report" => report_command(&args[1..]),
+        "list" => list_command(&args[1..]),
+        "pin" => pin_command(&args[1..], true),
+        "unpin" => pin_command(&args[1..], false),
+        "archive" => archive_command(&args[1..], true),
+        "unarchive" => archive_command(&args[1..], false),
+        "del" => del_command(&args[1..]),
+        other => Err(eyre!("Unknown command \"{}\"", other)),
+    }
+}
+
+/// One line of `note list` output: `<n>: <title> - <body>` with the extra
+/// fields the zsh script doesn't know about appended.
+fn list_line(i: usize, note: &NoteFormat) -> String {
+    let mut line = format!("{}: ", i);
+    if note.pinned {
+        line.push_str("[pin] ");
+    }
+    if note.done.is_some() {
+        line.push_str("[x] ");
+    }
+    if let Some(label) = note.label {
+        line.push_str(&format!("({}) ", label));
+    }
+    if !note.folder.is_empty() {
+        line.push_str(&format!("{}/ ", note.folder));
+    }
+    if note.priority != Priority::None {
+        line.push_str(&format!("[{}] ", note.priority));
+    }
+    line.push_str(&format!("{} - {}", note.text, note.body));
+    if let Some(due) = note.due {
+        line.push_str(&format!(" (due {})", due));
+    }
+    if !note.fields.is_empty() {
+        let fields: Vec<String> = note
+            .fields
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect();
+        line.push_str(&format!(" {{{}}}", fields.join(", ")));
+    }
+    line
+}
+
+/// `note list --json` form of a note; unset fields are `null`.
+fn note_json(i: usize, note: &NoteFormat) -> Value {
+    let fields: Map<String, Value> = note
+        .fields
+        .iter()
+        .map(|(key, value)| (key.to_string(), json!(value)))
+        .collect();
+    json!({
+        "number": i,
+        "title": note.text,
+        "body": note.body,
+        "folder": note.folder,
+        "priority": (note.priority != Priority::None).then(|| note.priority.to_string()),
+        "label": note.label.map(|label| label.to_string()),
+        "pinned": note.pinned,
+        "archived": note.archived,
+        "done": note.done.map(meta::format_datetime),
+        "due": note.due.map(|due| due.to_meta()),
+        "created": note.created.map(meta::format_datetime),
+        "fields": fields,
+        "attachments": note.attachments,
+    })
+}
+
+fn list_command(args: &[String]) -> Result<()> {
+    let notes = load_note_list()?;
+    let mut options = ViewOptions::default();
+    let mut title_filter: Option<String> = None;
+    let mut as_json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--archived" => options.panel = Panel::Archive,
+            "--sort" => {
+                let value = args.next().ok_or_else(|| eyre!("--sort needs a value"))?;
+                options.sort = SortMode::parse(value)
+                    .ok_or_else(|| eyre!("Unknown sort \"{}\" (manual, priority, due)", value))?;
+            }
+            "--field" => {
+                let value = args.next().ok_or_else(|| eyre!("--field needs a value"))?;
+                options.field =
+                    Some(FieldFilter::parse(value).ok_or_else(|| {
+                        eyre!("Bad field filter \"{}\" (key or key=value)", value)
+                    })?);
+            }
+            "--color" => {
+                let value = args.next().ok_or_else(|| eyre!("--color needs a value"))?;
+                options.label = Some(Label::parse(value).ok_or_else(|| {
+                    eyre!(
+                        "Unknown color \"{}\" (red, orange, yellow, green, blue, purple, gray)",
+                        value
+                    )
+                })?);
+            }
+            "--filter" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| eyre!("--filter needs an expression"))?;
+                options.filter = Some(
+                    Filter::parse(value, Local::now().naive_local()).map_err(|err| eyre!(err))?,
+                );
+            }
+            "--json" => as_json = true,
+            other if other.starts_with("--") => {
+                return Err(eyre!("Unknown option \"{}\"", other));
+            }
+            // a folder path if any note lives there, otherwise a title filter
+            other => {
+                let folder = tree::normalize(other);
+                if notes
+                    .iter()
+                    .any(|note| tree::contains(&folder, &note.folder))
+                {
+                    options.folder = Some(folder);
+                } else {
+                    title_filter = Some(other.to_lowercase());
+                }
+            }
+        }
+    }
+
+    let matching: Vec<usize> = view::visible(&notes, &options)
+        .into_iter()
+        .filter(|&i| {
+            title_filter
+                .as_ref()
+                .is_none_or(|filter| notes[i].text.to_lowercase().contains(filter))
+        })
+        .collect();
+    if as_json {
+        let list: Vec<Value> = matching.iter().map(|&i| note_json(i, &notes[i])).collect();
+        println!("{}", serde_json::to_string_pretty(&list)?);
+        return Ok(());
+    }
+
+    if notes.is_empty() {
+        println!("Note: No notes yet.");
+        return Ok(());
+    }
+    println!("Note:");
+    for i in matching {
+        println!("{}", list_line(i, &notes[i]));
+    }
+    Ok(())
+}
+
+/// `note add --template <name> [title] [body]`. Plain `note add` is handled
+/// by the zsh script; a title or body given here replaces the template's.
+fn add_command(args: &[String]) -> Result<()> {
+    let mut name: Option<&String> = None;
+    let mut positional: Vec<&String> = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--template" => {
+                name = Some(
+                    args.next()
+                        .ok_or_else(|| eyre!("--template needs a name"))?,
+                );
+            }
+            other if other.starts_with("--") => {
+                return Err(eyre!("Unknown option \"{}\"", other));
+            }
+            _ => positional.push(arg),
+        }
+    }
+    let name = name.ok_or_else(|| eyre!("Please provide a title or --template <name>."))?;
+    let dir = template::templates_dir(&file_path());
+    let templates = template::load_all(&dir);
+    let template = template::find(&templates, name).ok_or_else(|| {
+        let names: Vec<&str> = templates.iter().map(|t| t.name.as_str()).collect();
+        eyre!(
+            "No template \"{}\" in {} (have: {})",
+            name,
+            dir.display(),
+            if names.is_empty() {
+                "none".to_string()
+            } else {
+                names.join(", ")
+            }
+        )
+    })?;
+    let (mut title, mut body) = template.expand(Local::now().naive_local());
+    if let Some(arg) = positional.first() {
+        title = arg.to_string();
+    }
+    if let Some(arg) = positional.get(1) {
+        body = arg.to_string();
+    }
+    if title.trim().is_empty() {
+        return Err(eyre!("The note title is empty."));
+    }
+    if title.chars().count() > 100 || body.chars().count() > 100 {
+        return Err(eyre!("Title and body must be at most 100 characters."));
+    }
+    let mut notes = load_note_list()?;
+    add_note(
+        &mut notes,
+        NoteFormat {
+            text: title.clone(),
+            body: body.clone(),
+            ..Default::default()
+        },
+    )?;
+    println!("Note: Added \"{}\" - \"{}\"", title, body);
+    Ok(())
+}
+
+/// `note today [--template <name>]`: shows today's journal note, creating
+/// it first if there is none.
+fn today_command(args: &[String]) -> Result<()> {
+    let template_name = match args {
+        [] => None,
+        [flag, name] if flag == "--template" => Some(name.as_str()),
+        _ => return Err(eyre!("Usage: note today [--template <name>]")),
+    };
+    let mut notes = load_note_list()?;
+    let format = journal::date_format();
+    let now = Local::now().naive_local();
+    if let Some(i) = journal::find(&notes, now.date(), &format) {
+        println!("{}", list_line(i, &notes[i]));
+        return Ok(());
+    }
+
+    let templates = template::load_all(&template::templates_dir(&file_path()));
+    if let Some(name) = template_name {
+        if template::find(&templates, name).is_none() {
+            return Err(eyre!("No template \"{}\"", name));
+        }
+    }
+    let (title, body) = journal::new_entry(now.date(), now, &format, &templates, template_name);
+    let n = notes.len();
+    add_note(
+        &mut notes,
+        NoteFormat {
+            text: title.clone(),
+            body: body.clone(),
+            ..Default::default()
+        },
+    )?;
+    println!("Note: Created journal note number {}", n);
+    println!("{}: {} - {}", n, title, body);
+    Ok(())
+}
+
+/// `note report --time [--since <date>]`: time spent per note, most first.
+fn report_command(args: &[String]) -> Result<()> {
+    let now = Local::now().naive_local();
+    let mut time = false;
+    let mut since = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--time" => time = true,
+            "--since" => {
+                let value = args.next().ok_or_else(|| eyre!("--since needs a date"))?;
+                let date = timelog::parse_since(value, now)
+                    .ok_or_else(|| eyre!("Could not read the date \"{}\"", value))?;
+                since = Some(date.and_hms_opt(0, 0, 0).unwrap());
+            }
+            other => return Err(eyre!("Unknown option \"{}\"", other)),
+        }
+    }
+    if !time {
+        return Err(eyre!("Usage: note report --time [--since <date>]"));
+    }
+
+    let notes = load_note_list()?;
+    let mut totals: Vec<(usize, Duration)> = notes
+        .iter()
+        .enumerate()
+        .map(|(i, note)| (i, timelog::total(note, since, now)))
+        .filter(|(_, total)| *total > Duration::zero())
+        .collect();
+    totals.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+    match since {
+        Some(since) => println!("Note: Time spent since {}", since.date()),
+        None => println!("Note: Time spent"),
+    }
+    for (i, total) in &totals {
+        let running = if notes[*i].timer.is_some() {
+            " (running)"
+        } else {
+            ""
+        };
+        println!(
+            "{:>8}  {}: {}{}",
+            timelog::format_duration(*total),
+            i,
+            notes[*i].text,
+            running
+        );
+    }
+    let sum = totals
+        .iter()
+        .fold(Duration::zero(), |sum, (_, total)| sum + *total);
+    println!("{:>8}  total", timelog::format_duration(sum));
+    Ok(())
+}
+
+/// Parses a 0-based note number and checks that the note exists.
+fn note_number(arg: Option<&String>, notes: &[NoteFormat]) -> Result<usize> {
+    let arg = arg.ok_or_else(|| eyre!("Please provide a note number."))?;
+    match arg.parse::<usize>() {
+        Ok(n) if n < notes.len() => Ok(n),
+        _ => Err(eyre!("Note number {} does not exist.", arg)),
+    }
+}
+
+fn pin_command(args: &[String], pinned: bool) -> Result<()> {
+    let mut notes = load_note_list()?;
+    let n = note_number(args.first(), &notes)?;
+    notes[n].pinned = pinned;
+    save_meta(&notes)?;
+    if pinned {
+        println!("Note: Pinned note number {}", n);
+    } else {
+        println!("Note: Unpinned note number {}", n);
+    }
+    Ok(())
+}
+
+/// `note del <n>`, which also renumbers the `[[n]]` links to the notes
+/// after it.
+fn del_command(args: &[String]) -> Result<()> {
+    let mut notes = load_note_list()?;
+    let n = note_number(args.first(), &notes)?;
+    let (_, dropped) = Change::delete(&mut notes, n);
+    save_notes(&notes)?;
+    println!("Note: Deleted note number {}", n);
+    for i in dropped {
+        println!(
+            "Note: Removed the link to it from note number {}, its title didn't fit",
+            i
+        );
+    }
+    Ok(())
+}
+
+fn archive_command(args: &[String], archived: bool) -> Result<()> {
+    let mut notes = load_note_list()?;
+    if archived && args.first().is_some_and(|arg| arg == "--auto") {
+        let days = match args.get(1) {
+            Some(days) => days
+                .parse()
+                .map_err(|_| eyre!("Days must be a number, got \"{}\"", days))?,
+            None => auto_archive_days(),
+        };
+        let count = auto_archive(&mut notes, Local::now().naive_local(), days);
+        save_meta(&notes)?;
+        println!(
+            "Note: Archived {} note(s) done more than {} days ago",
+            count, days
+        );
+        return Ok(());
+    }
+
+    let n = note_number(args.first(), &notes)?;
+    notes[n].archived = archived;
+    save_meta(&notes)?;
+    if archived {
+        println!("Note: Archived note number {}", n);
+    } else {
+        println!("Note: Unarchived note number {}", n);
+    }
+    Ok(())
+}
+
+/// `note attach <n> <path>...` attaches files; `note attach <n>` prints the
+/// attached paths, flagging missing files.
+fn attach_command(args: &[String]) -> Result<()> {
+    let mut notes = load_note_list()?;
+    let n = note_number(args.first(), &notes)?;
+    let paths = &args[1..];
+    if paths.is_empty() {
+        if notes[n].attachments.is_empty() {
+            println!("Note: Note number {} has no attachments.", n);
+        }
+        for path in &notes[n].attachments {
+            if attach::is_missing(path) {
+                println!("{} (missing)", path);
+            } else {
+                println!("{}", path);
+            }
+        }
+        return Ok(());
+    }
+
+    for path in paths {
+        let path = attach::absolute(path);
+        if !path.exists() {
+            return Err(eyre!("{} does not exist.", path.display()));
+        }
+        let path = path.display().to_string();
+        if !notes[n].attachments.contains(&path) {
+            println!("Note: Attached {} to note number {}", path, n);
+            notes[n].attachments.push(path);
+        }
+    }
+    save_meta(&notes)?;
+    Ok(())
+}
+
+fn agenda_command() -> Result<()> {
+    let notes = load_note_list()?;
+    let groups = agenda::group(&notes, Local::now().naive_local());
+    if groups.iter().all(|(_, entries)| entries.is_empty()) {
+        println!("Note: No notes with a due date.");
+        return Ok(());
+    }
+    for (bucket, entries) in groups {
+        if entries.is_empty() {
+            continue;
+        }
+        println!("{}:", bucket.label());
+        for (i, note) in entries {
+            println!("  {}", list_line(i, note));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,570 @@
+//! Natural-language date entry, used for due dates and date filters.
+//!
+//! Everything is resolved relative to a `now` passed in by the caller, so the
+//! TUI can show a live preview and the tests can pin the clock. Accepted
+//! forms, which can be combined as `<date> <time>` in either order:
+//!
+//! - ISO dates: `2026-11-02`, `2026/11/02`, and `11/2` or `nov 2` for the
+//!   next such day
+//! - `today`, `tomorrow`, `yesterday`, `day after tomorrow`
+//! - `in 3 days`, `in 2 weeks`, `in 1 month`, `3 days`, `+3d`, `+2w`
+//! - `fri` (next Friday after today), `this fri` (today or later),
+//!   `next fri` (Friday of next week), `next week`, `next month`
+//! - times: `9am`, `9:30pm`, `21:00`, `9 am`, `noon`, `midnight`
+//! - Japanese: `今日`, `明日`, `明後日`, `昨日`, `来週`, `来週の金曜`,
+//!   `金曜日`, `来月`, `3日後`, `2週間後`, `1ヶ月後`, `11月2日`, `9時`,
+//!   `9時30分`, `午後3時半`, `正午`
+//!
+//! A time without a date means today.
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+
+use crate::agenda::Due;
+
+/// Words that carry no meaning on their own ("due at 9am", "by fri").
+const FILLER: [&str; 5] = ["at", "on", "by", "due", "the"];
+
+/// Japanese words replaced by their English equivalents before tokenizing.
+/// Longer words come first so `明後日` is not read as `明` + `後日`.
+const JAPANESE_WORDS: [(&str, &str); 31] = [
+    ("明後日", " dayaftertomorrow "),
+    ("あさって", " dayaftertomorrow "),
+    ("明日", " tomorrow "),
+    ("あした", " tomorrow "),
+    ("今日", " today "),
+    ("きょう", " today "),
+    ("昨日", " yesterday "),
+    ("来週", " next week "),
+    ("今週", " this week "),
+    ("来月", " next month "),
+    ("月曜日", " mon "),
+    ("火曜日", " tue "),
+    ("水曜日", " wed "),
+    ("木曜日", " thu "),
+    ("金曜日", " fri "),
+    ("土曜日", " sat "),
+    ("日曜日", " sun "),
+    ("月曜", " mon "),
+    ("火曜", " tue "),
+    ("水曜", " wed "),
+    ("木曜", " thu "),
+    ("金曜", " fri "),
+    ("土曜", " sat "),
+    ("日曜", " sun "),
+    ("午前", " am "),
+    ("午後", " pm "),
+    ("正午", " noon "),
+    ("まで", " "),
+    ("の", " "),
+    ("に", " "),
+    ("、", " "),
+];
+
+pub fn parse(input: &str, now: NaiveDateTime) -> Option<Due> {
+    if let Some(due) = Due::parse(input) {
+        return Some(due);
+    }
+
+    let normalized = normalize(input);
+    let tokens: Vec<&str> = normalized
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|t| !t.is_empty() && !FILLER.contains(t))
+        .collect();
+    if tokens.is_empty() {
+        return None;
+    }
+
+    let today = now.date();
+    let mut date = None;
+    let mut time = None;
+    let mut i = 0;
+    while i < tokens.len() {
+        let rest = &tokens[i..];
+        if let Some((d, used)) = parse_date(rest, today) {
+            if date.replace(d).is_some() {
+                return None;
+            }
+            i += used;
+        } else if let Some((t, used)) = parse_time(rest) {
+            if time.replace(t).is_some() {
+                return None;
+            }
+            i += used;
+        } else {
+            return None;
+        }
+    }
+
+    Some(Due {
+        date: date.unwrap_or(today),
+        time,
+    })
+}
+
+/// Lowercases, folds full-width digits and rewrites Japanese expressions into
+/// the English tokens `parse_date` and `parse_time` understand.
+fn normalize(input: &str) -> String {
+    let mut s: String = input
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            '０'..='９' => char::from(b'0' + (c as u32 - '０' as u32) as u8),
+            '：' => ':',
+            '／' => '/',
+            '　' => ' ',
+            c => c,
+        })
+        .collect();
+    for (word, replacement) in JAPANESE_WORDS {
+        s = s.replace(word, replacement);
+    }
+    rewrite_japanese_numbers(&s)
+}
+
+/// Rewrites `<n>日後`, `<n>週間後`, `<n>ヶ月後`, `<m>月<d>日`, `<h>時`,
+/// `<h>時<m>分` and `<h>時半`.
+fn rewrite_japanese_numbers(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+        out.push_str(&rest[..start]);
+        let (n, after) = split_number(&rest[start..]);
+
+        if let Some(after) = after.strip_prefix("日後") {
+            out.push_str(&format!(" in {} days ", n));
+            rest = after;
+        } else if let Some(after) = after.strip_prefix("週間後") {
+            out.push_str(&format!(" in {} weeks ", n));
+            rest = after;
+        } else if let Some(after) = ["ヶ月後", "か月後", "カ月後", "ヵ月後"]
+            .iter()
+            .find_map(|suffix| after.strip_prefix(suffix))
+        {
+            out.push_str(&format!(" in {} months ", n));
+            rest = after;
+        } else if let Some((day, after)) = after
+            .strip_prefix('月')
+            .map(split_number)
+            .filter(|(day, _)| !day.is_empty())
+            .and_then(|(day, after)| Some((day, after.strip_prefix('日')?)))
+        {
+            out.push_str(&format!(" {}/{} ", n, day));
+            rest = after;
+        } else if let Some(after) = after.strip_prefix("時半") {
+            out.push_str(&format!(" {}:30 ", n));
+            rest = after;
+        } else if let Some(after) = after.strip_prefix('時') {
+            let (minute, after_minute) = split_number(after);
+            match after_minute.strip_prefix('分') {
+                Some(after) if !minute.is_empty() => {
+                    out.push_str(&format!(" {}:{:0>2} ", n, minute));
+                    rest = after;
+                }
+                _ => {
+                    out.push_str(&format!(" {}:00 ", n));
+                    rest = after;
+                }
+            }
+        } else {
+            out.push_str(n);
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn split_number(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// Tries to read a date from the start of `tokens`, returning it with the
+/// number of tokens consumed.
+fn parse_date(tokens: &[&str], today: NaiveDate) -> Option<(NaiveDate, usize)> {
+    let first = tokens[0];
+    let second = tokens.get(1).copied();
+
+    match first {
+        "today" | "tod" => return Some((today, 1)),
+        "tomorrow" | "tmr" | "tmrw" => return Some((today + Duration::days(1), 1)),
+        "yesterday" => return Some((today - Duration::days(1), 1)),
+        "dayaftertomorrow" => return Some((today + Duration::days(2), 1)),
+        "day" if second == Some("after") && tokens.get(2) == Some(&"tomorrow") => {
+            return Some((today + Duration::days(2), 3));
+        }
+        "in" => {
+            let count = match second? {
+                "a" | "an" | "one" => 1,
+                n => n.parse().ok()?,
+            };
+            return Some((shift(today, count, tokens.get(2)?)?, 3));
+        }
+        "next" => {
+            return match second? {
+                "week" => match tokens.get(2).and_then(|t| weekday(t)) {
+                    Some(wd) => Some((next_week_day(today, wd), 3)),
+                    None => Some((next_week_day(today, Weekday::Mon), 2)),
+                },
+                "month" => {
+                    let first_of_month = today.with_day(1)?;
+                    Some((first_of_month.checked_add_months(Months::new(1))?, 2))
+                }
+                t => Some((next_week_day(today, weekday(t)?), 2)),
+            };
+        }
+        "this" => {
+            return match second? {
+                "week" => Some((on_or_after(today, weekday(tokens.get(2)?)?), 3)),
+                t => Some((on_or_after(today, weekday(t)?), 2)),
+            };
+        }
+        _ => {}
+    }
+
+    if let Some(wd) = weekday(first) {
+        return Some((on_or_after(today + Duration::days(1), wd), 1));
+    }
+    if let Some(relative) = first.strip_prefix('+') {
+        let (n, unit) = split_number(relative);
+        return Some((shift(today, n.parse().ok()?, unit)?, 1));
+    }
+    if let Ok(n) = first.parse::<i64>() {
+        if let Some(date) = second.and_then(|unit| shift(today, n, unit)) {
+            return Some((date, 2));
+        }
+        if let Some(month) = second.and_then(month) {
+            return Some((next_month_day(today, month, u32::try_from(n).ok()?)?, 2));
+        }
+        return None;
+    }
+    if let Some(month) = month(first) {
+        let day = second?.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+        return Some((next_month_day(today, month, day.parse().ok()?)?, 2));
+    }
+    parse_numeric_date(first, today).map(|date| (date, 1))
+}
+
+/// `2026-11-02`, `2026/11/02`, `11/2` and `11-2`.
+fn parse_numeric_date(token: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let parts: Vec<&str> = token.split(['-', '/']).collect();
+    match parts.as_slice() {
+        [y, m, d] if y.len() == 4 => {
+            NaiveDate::from_ymd_opt(y.parse().ok()?, m.parse().ok()?, d.parse().ok()?)
+        }
+        [m, d] => next_month_day(today, m.parse().ok()?, d.parse().ok()?),
+        _ => None,
+    }
+}
+
+/// Tries to read a time from the start of `tokens`.
+fn parse_time(tokens: &[&str]) -> Option<(NaiveTime, usize)> {
+    match tokens[0] {
+        "noon" => return Some((NaiveTime::from_hms_opt(12, 0, 0)?, 1)),
+        "midnight" => return Some((NaiveTime::from_hms_opt(0, 0, 0)?, 1)),
+        // 午前/午後 come before the time in Japanese
+        meridiem @ ("am" | "pm") => {
+            let time = clock(tokens.get(1)?, Some(meridiem))?;
+            return Some((time, 2));
+        }
+        _ => {}
+    }
+
+    let token = tokens[0];
+    for meridiem in ["am", "pm"] {
+        if let Some(clock_part) = token.strip_suffix(meridiem) {
+            return Some((clock(clock_part, Some(meridiem))?, 1));
+        }
+    }
+    if let Some(&meridiem @ ("am" | "pm")) = tokens.get(1) {
+        return Some((clock(token, Some(meridiem))?, 2));
+    }
+    if token.contains(':') {
+        return Some((clock(token, None)?, 1));
+    }
+    None
+}
+
+/// `9`, `9:30` or `21:00`, adjusted for a trailing `am`/`pm`.
+fn clock(s: &str, meridiem: Option<&str>) -> Option<NaiveTime> {
+    let (hour, minute) = match s.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None => (s.parse::<u32>().ok()?, 0),
+    };
+    let hour = match meridiem {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some("am") => hour % 12,
+        Some(_) => hour % 12 + 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// `date` moved `n` units on, or `None` when that is past the dates
+/// chrono can represent.
+fn shift(date: NaiveDate, n: i64, unit: &str) -> Option<NaiveDate> {
+    match unit {
+        "d" | "day" | "days" => date.checked_add_signed(Duration::try_days(n)?),
+        "w" | "wk" | "week" | "weeks" => date.checked_add_signed(Duration::try_weeks(n)?),
+        "m" | "mo" | "month" | "months" => date.checked_add_months(Months::new(n.try_into().ok()?)),
+        "y" | "year" | "years" => {
+            date.checked_add_months(Months::new(u32::try_from(n).ok()?.checked_mul(12)?))
+        }
+        _ => None,
+    }
+}
+
+fn weekday(token: &str) -> Option<Weekday> {
+    let weekday = match token {
+        "mon" | "monday" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" => Weekday::Tue,
+        "wed" | "wednesday" => Weekday::Wed,
+        "thu" | "thur" | "thurs" | "thursday" => Weekday::Thu,
+        "fri" | "friday" => Weekday::Fri,
+        "sat" | "saturday" => Weekday::Sat,
+        "sun" | "sunday" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(weekday)
+}
+
+fn month(token: &str) -> Option<u32> {
+    let month = match token {
+        "jan" | "january" => 1,
+        "feb" | "february" => 2,
+        "mar" | "march" => 3,
+        "apr" | "april" => 4,
+        "may" => 5,
+        "jun" | "june" => 6,
+        "jul" | "july" => 7,
+        "aug" | "august" => 8,
+        "sep" | "sept" | "september" => 9,
+        "oct" | "october" => 10,
+        "nov" | "november" => 11,
+        "dec" | "december" => 12,
+        _ => return None,
+    };
+    Some(month)
+}
+
+fn on_or_after(date: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (7 + weekday.num_days_from_monday() as i64
+        - date.weekday().num_days_from_monday() as i64)
+        % 7;
+    date + Duration::days(ahead)
+}
+
+/// The given weekday in the Monday-to-Sunday week after the current one.
+fn next_week_day(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let next_monday = on_or_after(today + Duration::days(1), Weekday::Mon);
+    next_monday + Duration::days(weekday.num_days_from_monday() as i64)
+}
+
+/// The next `month`/`day` on or after today, rolling over into next year.
+fn next_month_day(today: NaiveDate, month: u32, day: u32) -> Option<NaiveDate> {
+    let this_year = NaiveDate::from_ymd_opt(today.year(), month, day);
+    match this_year {
+        Some(date) if date >= today => Some(date),
+        _ => NaiveDate::from_ymd_opt(today.year() + 1, month, day),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday 2026-10-21 10:30.
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 21)
+            .unwrap()
+            .and_hms_opt(10, 30, 0)
+            .unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn on(input: &str) -> (NaiveDate, Option<NaiveTime>) {
+        let due = parse(input, now()).unwrap_or_else(|| panic!("failed to parse {:?}", input));
+        (due.date, due.time)
+    }
+
+    #[test]
+    fn huge_offsets_are_not_dates() {
+        for input in [
+            "in 200000000000000 days",
+            "in 200000000000000 weeks",
+            "in 99999999 days",
+            "in 4294967295 years",
+            "+200000000000000d",
+        ] {
+            assert_eq!(parse(input, now()), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn days_past_u32_are_not_wrapped() {
+        // 4294967298 is 2 once cut to 32 bits
+        for input in ["4294967298 nov", "nov 4294967298", "-1 nov"] {
+            assert_eq!(parse(input, now()), None, "{:?}", input);
+        }
+        assert_eq!(on("2 nov"), (date(2026, 11, 2), None));
+    }
+
+    #[test]
+    fn iso_dates() {
+        assert_eq!(on("2026-11-02"), (date(2026, 11, 2), None));
+        assert_eq!(on("2026/11/02"), (date(2026, 11, 2), None));
+        assert_eq!(
+            on("2026-11-02 09:15"),
+            (date(2026, 11, 2), Some(time(9, 15)))
+        );
+        assert_eq!(
+            on("2026-11-02T09:15"),
+            (date(2026, 11, 2), Some(time(9, 15)))
+        );
+    }
+
+    #[test]
+    fn month_and_day_roll_into_next_year() {
+        assert_eq!(on("11/2"), (date(2026, 11, 2), None));
+        assert_eq!(on("10/21"), (date(2026, 10, 21), None));
+        assert_eq!(on("1/5"), (date(2027, 1, 5), None));
+        assert_eq!(on("nov 2"), (date(2026, 11, 2), None));
+        assert_eq!(on("2 nov"), (date(2026, 11, 2), None));
+        assert_eq!(on("march 3rd"), (date(2027, 3, 3), None));
+    }
+
+    #[test]
+    fn relative_days() {
+        assert_eq!(on("today"), (date(2026, 10, 21), None));
+        assert_eq!(on("Tomorrow"), (date(2026, 10, 22), None));
+        assert_eq!(on("yesterday"), (date(2026, 10, 20), None));
+        assert_eq!(on("day after tomorrow"), (date(2026, 10, 23), None));
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(on("in 3 days"), (date(2026, 10, 24), None));
+        assert_eq!(on("in a week"), (date(2026, 10, 28), None));
+        assert_eq!(on("in 2 weeks"), (date(2026, 11, 4), None));
+        assert_eq!(on("in 1 month"), (date(2026, 11, 21), None));
+        assert_eq!(on("3 days"), (date(2026, 10, 24), None));
+        assert_eq!(on("+3d"), (date(2026, 10, 24), None));
+        assert_eq!(on("+2w"), (date(2026, 11, 4), None));
+    }
+
+    #[test]
+    fn weekdays() {
+        // bare weekday: the next one after today
+        assert_eq!(on("fri"), (date(2026, 10, 23), None));
+        assert_eq!(on("wednesday"), (date(2026, 10, 28), None));
+        assert_eq!(on("mon"), (date(2026, 10, 26), None));
+        // this: today or later
+        assert_eq!(on("this wed"), (date(2026, 10, 21), None));
+        assert_eq!(on("this fri"), (date(2026, 10, 23), None));
+        // next: in the following Monday-Sunday week
+        assert_eq!(on("next fri"), (date(2026, 10, 30), None));
+        assert_eq!(on("next mon"), (date(2026, 10, 26), None));
+        assert_eq!(on("next week"), (date(2026, 10, 26), None));
+        assert_eq!(on("next month"), (date(2026, 11, 1), None));
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(on("9am"), (date(2026, 10, 21), Some(time(9, 0))));
+        assert_eq!(on("9:30pm"), (date(2026, 10, 21), Some(time(21, 30))));
+        assert_eq!(on("9 pm"), (date(2026, 10, 21), Some(time(21, 0))));
+        assert_eq!(on("21:00"), (date(2026, 10, 21), Some(time(21, 0))));
+        assert_eq!(on("12am"), (date(2026, 10, 21), Some(time(0, 0))));
+        assert_eq!(on("12pm"), (date(2026, 10, 21), Some(time(12, 0))));
+        assert_eq!(on("noon"), (date(2026, 10, 21), Some(time(12, 0))));
+        assert_eq!(on("midnight"), (date(2026, 10, 21), Some(time(0, 0))));
+    }
+
+    #[test]
+    fn date_and_time_combined() {
+        assert_eq!(on("tomorrow 9am"), (date(2026, 10, 22), Some(time(9, 0))));
+        assert_eq!(on("9am tomorrow"), (date(2026, 10, 22), Some(time(9, 0))));
+        assert_eq!(
+            on("next fri at 5pm"),
+            (date(2026, 10, 30), Some(time(17, 0)))
+        );
+        assert_eq!(
+            on("due by fri, noon"),
+            (date(2026, 10, 23), Some(time(12, 0)))
+        );
+        assert_eq!(
+            on("in 3 days 14:45"),
+            (date(2026, 10, 24), Some(time(14, 45)))
+        );
+    }
+
+    #[test]
+    fn japanese_days() {
+        assert_eq!(on("今日"), (date(2026, 10, 21), None));
+        assert_eq!(on("明日"), (date(2026, 10, 22), None));
+        assert_eq!(on("明後日"), (date(2026, 10, 23), None));
+        assert_eq!(on("昨日"), (date(2026, 10, 20), None));
+        assert_eq!(on("来週"), (date(2026, 10, 26), None));
+        assert_eq!(on("来月"), (date(2026, 11, 1), None));
+    }
+
+    #[test]
+    fn japanese_weekdays() {
+        assert_eq!(on("金曜日"), (date(2026, 10, 23), None));
+        assert_eq!(on("金曜"), (date(2026, 10, 23), None));
+        assert_eq!(on("来週金曜"), (date(2026, 10, 30), None));
+        assert_eq!(on("来週の金曜日"), (date(2026, 10, 30), None));
+        assert_eq!(on("今週の金曜"), (date(2026, 10, 23), None));
+        assert_eq!(on("日曜"), (date(2026, 10, 25), None));
+    }
+
+    #[test]
+    fn japanese_offsets_and_dates() {
+        assert_eq!(on("3日後"), (date(2026, 10, 24), None));
+        assert_eq!(on("2週間後"), (date(2026, 11, 4), None));
+        assert_eq!(on("1ヶ月後"), (date(2026, 11, 21), None));
+        assert_eq!(on("11月2日"), (date(2026, 11, 2), None));
+        assert_eq!(on("１１月２日"), (date(2026, 11, 2), None));
+    }
+
+    #[test]
+    fn japanese_times() {
+        assert_eq!(on("明日9時"), (date(2026, 10, 22), Some(time(9, 0))));
+        assert_eq!(on("明日の9時30分"), (date(2026, 10, 22), Some(time(9, 30))));
+        assert_eq!(on("午後3時"), (date(2026, 10, 21), Some(time(15, 0))));
+        assert_eq!(on("午後3時半"), (date(2026, 10, 21), Some(time(15, 30))));
+        assert_eq!(on("午前9時"), (date(2026, 10, 21), Some(time(9, 0))));
+        assert_eq!(
+            on("来週金曜の正午"),
+            (date(2026, 10, 30), Some(time(12, 0)))
+        );
+        assert_eq!(on("明日 15:00"), (date(2026, 10, 22), Some(time(15, 0))));
+    }
+
+    #[test]
+    fn rejects_nonsense() {
+        for input in [
+            "",
+            "   ",
+            "soon",
+            "next",
+            "in days",
+            "2026-13-01",
+            "2/30",
+            "25:00",
+            "13pm",
+            "tomorrow today",
+            "9am 10am",
+            "来年",
+        ] {
+            assert_eq!(parse(input, now()), None, "{:?} should not parse", input);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Custom `key: value` fields such as `ticket: OPS-123` or `owner: infra`.
+//!
+//! Fields keep the order they were added in. Keys are compared ignoring
+//! case, so `Owner` and `owner` are the same field.
+
+use std::fmt;
+
+/// The custom fields of one note, in insertion order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fields(Vec<(String, String)>);
+
+impl Fields {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Sets `key`, keeping its position if it already exists.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.0.iter_mut().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.0.push((key.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.0.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Splits `ticket: OPS-123` (or `ticket=OPS-123`) into key and value. The
+/// value may be empty, which the field popup uses to remove a field.
+pub fn parse_entry(s: &str) -> Option<(String, String)> {
+    let (key, value) = s.split_once([':', '='])?;
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key.to_string(), value.trim().to_string()))
+}
+
+/// Fields shown as columns in the TUI list, from `NOTE_COLUMNS`
+/// (comma separated, e.g. `ticket,owner`).
+pub fn columns() -> Vec<String> {
+    std::env::var("NOTE_COLUMNS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// `key` matches notes that have the field, `key=value` (or `key:value`)
+/// notes where it equals `value`, ignoring case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldFilter {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl FieldFilter {
+    pub fn parse(s: &str) -> Option<FieldFilter> {
+        match parse_entry(s) {
+            Some((key, value)) => Some(FieldFilter {
+                key,
+                value: Some(value),
+            }),
+            None => {
+                let key = s.trim();
+                (!key.is_empty() && !key.contains(char::is_whitespace)).then(|| FieldFilter {
+                    key: key.to_string(),
+                    value: None,
+                })
+            }
+        }
+    }
+
+    pub fn matches(&self, fields: &Fields) -> bool {
+        match (fields.get(&self.key), &self.value) {
+            (Some(actual), Some(value)) => actual.eq_ignore_ascii_case(value),
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+impl fmt::Display for FieldFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.key, value),
+            None => write!(f, "{}", self.key),
+        }
+    }
+}
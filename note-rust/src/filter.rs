@@ -0,0 +1,391 @@
+//! Filter expressions for the TUI filter bar and `note list --filter`.
+//!
+//! An expression is a list of terms that must all match, such as
+//! `title:deploy body:"rollback" -tag:done created>2026-01-01`:
+//!
+//! - `word` or `"some words"`: in the title or body
+//! - `title:text`, `body:text`: in that part only
+//! - `tag:done`: status, one of `done`, `open`, `pinned`, `archived`,
+//!   `recurring`, `overdue`, `due`, `timer`, `attached`
+//! - `folder:work/infra`: in the folder or below it
+//! - `color:red` (or `label:red`): with that color label
+//! - `priority:high`, `priority>=medium`
+//! - `created`, `due` and `done` compared with `:`, `<`, `<=`, `>` or `>=`
+//!   against anything `dateparse` reads, e.g. `due<friday`
+//! - any other `key:value`: the custom field equals the value, and
+//!   `key:` alone means the field is set
+//!
+//! Text matches ignore case. A leading `-` negates a term.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use std::fmt;
+
+use crate::label::Label;
+use crate::priority::Priority;
+use crate::{dateparse, search, tree, NoteFormat};
+
+#[derive(Clone, Debug)]
+pub struct Filter {
+    source: String,
+    terms: Vec<Term>,
+}
+
+#[derive(Clone, Debug)]
+struct Term {
+    negate: bool,
+    cond: Cond,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn test<T: Ord>(self, a: T, b: T) -> bool {
+        match self {
+            Cmp::Eq => a == b,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum DateKey {
+    Created,
+    Due,
+    Done,
+}
+
+#[derive(Clone, Debug)]
+enum Cond {
+    Text(String),
+    Title(String),
+    Body(String),
+    Tag(Tag),
+    Folder(String),
+    Label(Label),
+    Priority(Cmp, Priority),
+    Date(DateKey, Cmp, NaiveDate),
+    Field(String, Option<String>),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Tag {
+    Done,
+    Open,
+    Pinned,
+    Archived,
+    Recurring,
+    Overdue,
+    Due,
+    Timer,
+    Attached,
+}
+
+impl Filter {
+    /// Parses `source`, resolving relative dates against `now`. The error
+    /// names the term that could not be read.
+    pub fn parse(source: &str, now: NaiveDateTime) -> Result<Filter, String> {
+        let terms = tokenize(source)?
+            .iter()
+            .map(|token| parse_term(token, now))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Filter {
+            source: source.trim().to_string(),
+            terms,
+        })
+    }
+
+    pub fn matches(&self, note: &NoteFormat, now: NaiveDateTime) -> bool {
+        self.terms
+            .iter()
+            .all(|term| term.cond.matches(note, now) != term.negate)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Cond {
+    fn matches(&self, note: &NoteFormat, now: NaiveDateTime) -> bool {
+        let contains =
+            |text: &str, query: &str| query.is_empty() || !search::find_all(text, query).is_empty();
+        match self {
+            Cond::Text(text) => contains(&note.text, text) || contains(&note.body, text),
+            Cond::Title(text) => contains(&note.text, text),
+            Cond::Body(text) => contains(&note.body, text),
+            Cond::Tag(tag) => match tag {
+                Tag::Done => note.done.is_some(),
+                Tag::Open => note.done.is_none(),
+                Tag::Pinned => note.pinned,
+                Tag::Archived => note.archived,
+                Tag::Recurring => note.recur.is_some(),
+                Tag::Overdue => {
+                    note.done.is_none() && note.due.is_some_and(|due| due.is_overdue(now))
+                }
+                Tag::Due => note.due.is_some(),
+                Tag::Timer => note.timer.is_some(),
+                Tag::Attached => !note.attachments.is_empty(),
+            },
+            Cond::Folder(folder) => tree::contains(folder, &note.folder),
+            Cond::Label(label) => note.label == Some(*label),
+            Cond::Priority(cmp, priority) => cmp.test(note.priority, *priority),
+            Cond::Date(key, cmp, date) => {
+                let value = match key {
+                    DateKey::Created => note.created.map(|dt| dt.date()),
+                    DateKey::Due => note.due.map(|due| due.date),
+                    DateKey::Done => note.done.map(|dt| dt.date()),
+                };
+                value.is_some_and(|value| cmp.test(value, *date))
+            }
+            Cond::Field(key, value) => match (note.fields.get(key), value) {
+                (Some(actual), Some(value)) => actual.eq_ignore_ascii_case(value),
+                (Some(_), None) => true,
+                (None, _) => false,
+            },
+        }
+    }
+}
+
+/// Splits on whitespace, keeping `"quoted text"` together and dropping the
+/// quotes, so `body:"roll back"` is one token.
+fn tokenize(source: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in source.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unclosed quote".to_string());
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn parse_term(token: &str, now: NaiveDateTime) -> Result<Term, String> {
+    let (negate, token) = match token.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+    let Some(op_start) = token.find([':', '<', '>']) else {
+        return Ok(Term {
+            negate,
+            cond: Cond::Text(token.to_string()),
+        });
+    };
+    let key = token[..op_start].to_lowercase();
+    let rest = &token[op_start..];
+    let (cmp, value) = if let Some(value) = rest.strip_prefix("<=") {
+        (Cmp::Le, value)
+    } else if let Some(value) = rest.strip_prefix(">=") {
+        (Cmp::Ge, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Cmp::Lt, value)
+    } else if let Some(value) = rest.strip_prefix('>') {
+        (Cmp::Gt, value)
+    } else {
+        (Cmp::Eq, &rest[1..])
+    };
+    let only_eq = |cond: Cond| {
+        if cmp == Cmp::Eq {
+            Ok(cond)
+        } else {
+            Err(format!("\"{}\" can only be used with \":\"", key))
+        }
+    };
+
+    let cond = match key.as_str() {
+        "" => return Err(format!("Missing key in \"{}\"", token)),
+        "title" => only_eq(Cond::Title(value.to_string()))?,
+        "body" => only_eq(Cond::Body(value.to_string()))?,
+        "tag" | "is" => only_eq(Cond::Tag(parse_tag(value)?))?,
+        "folder" => only_eq(Cond::Folder(tree::normalize(value)))?,
+        "color" | "label" => only_eq(Cond::Label(
+            Label::parse(value).ok_or_else(|| format!("Unknown color \"{}\"", value))?,
+        ))?,
+        "priority" | "pri" => Cond::Priority(
+            cmp,
+            Priority::parse(value).ok_or_else(|| format!("Unknown priority \"{}\"", value))?,
+        ),
+        "created" | "due" | "done" => {
+            let date = dateparse::parse(value, now)
+                .ok_or_else(|| format!("Could not read the date \"{}\"", value))?
+                .date;
+            let key = match key.as_str() {
+                "created" => DateKey::Created,
+                "due" => DateKey::Due,
+                _ => DateKey::Done,
+            };
+            Cond::Date(key, cmp, date)
+        }
+        _ => only_eq(Cond::Field(
+            token[..op_start].to_string(),
+            (!value.is_empty()).then(|| value.to_string()),
+        ))?,
+    };
+    Ok(Term { negate, cond })
+}
+
+fn parse_tag(value: &str) -> Result<Tag, String> {
+    match value.to_lowercase().as_str() {
+        "done" => Ok(Tag::Done),
+        "open" => Ok(Tag::Open),
+        "pinned" => Ok(Tag::Pinned),
+        "archived" => Ok(Tag::Archived),
+        "recurring" => Ok(Tag::Recurring),
+        "overdue" => Ok(Tag::Overdue),
+        "due" => Ok(Tag::Due),
+        "timer" => Ok(Tag::Timer),
+        "attached" => Ok(Tag::Attached),
+        _ => Err(format!("Unknown tag \"{}\"", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agenda::Due;
+
+    /// Wednesday 2026-10-21 10:30.
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 21)
+            .unwrap()
+            .and_hms_opt(10, 30, 0)
+            .unwrap()
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn note(text: &str, body: &str) -> NoteFormat {
+        NoteFormat {
+            text: text.to_string(),
+            body: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn matches(source: &str, note: &NoteFormat) -> bool {
+        Filter::parse(source, now())
+            .unwrap_or_else(|err| panic!("failed to parse {:?}: {}", source, err))
+            .matches(note, now())
+    }
+
+    fn error(source: &str) -> String {
+        Filter::parse(source, now()).unwrap_err()
+    }
+
+    #[test]
+    fn tokens_split_on_spaces_outside_quotes() {
+        assert_eq!(
+            tokenize(r#"  deploy body:"roll back"  -"two words" "#).unwrap(),
+            ["deploy", "body:roll back", "-two words"]
+        );
+        assert_eq!(tokenize("").unwrap(), Vec::<String>::new());
+        assert_eq!(
+            tokenize(r#"body:"roll back"#),
+            Err("Unclosed quote".to_string())
+        );
+    }
+
+    #[test]
+    fn terms() {
+        let term = parse_term("-title:Plan", now()).unwrap();
+        assert!(term.negate);
+        assert!(matches!(term.cond, Cond::Title(ref text) if text == "Plan"));
+        // a lone "-" is a word, not a negation
+        let term = parse_term("-", now()).unwrap();
+        assert!(!term.negate);
+        assert!(matches!(term.cond, Cond::Text(ref text) if text == "-"));
+        // the key is case-insensitive, custom field names keep their case
+        assert!(matches!(
+            parse_term("PRIORITY>=medium", now()).unwrap().cond,
+            Cond::Priority(Cmp::Ge, Priority::Medium)
+        ));
+        assert!(matches!(
+            parse_term("Owner:", now()).unwrap().cond,
+            Cond::Field(ref key, None) if key == "Owner"
+        ));
+        assert!(matches!(
+            parse_term("due<=friday", now()).unwrap().cond,
+            Cond::Date(DateKey::Due, Cmp::Le, d) if d == date(2026, 10, 23)
+        ));
+        assert!(matches!(
+            parse_term("created>2026-01-01", now()).unwrap().cond,
+            Cond::Date(DateKey::Created, Cmp::Gt, d) if d == date(2026, 1, 1)
+        ));
+    }
+
+    #[test]
+    fn all_terms_must_match_and_negation_binds_to_one_term() {
+        let mut n = note("Deploy plan", "roll back first");
+        n.pinned = true;
+        assert!(matches("deploy tag:pinned", &n));
+        assert!(!matches("deploy -tag:pinned", &n));
+        assert!(matches("-tag:done tag:pinned", &n));
+        assert!(!matches("deploy -rollback -\"roll back\"", &n));
+        assert!(matches("", &n));
+        // quoted text is one phrase, unquoted words are separate terms
+        assert!(matches("back roll", &n));
+        assert!(!matches("\"back roll\"", &n));
+    }
+
+    #[test]
+    fn conditions() {
+        let mut n = note("Plan", "Ship it");
+        n.folder = "work/infra".to_string();
+        n.priority = Priority::High;
+        n.label = Label::parse("red");
+        n.due = Some(Due {
+            date: date(2026, 10, 20),
+            time: None,
+        });
+        n.fields.set("owner", "Ana");
+        assert!(matches("title:plan body:ship", &n));
+        assert!(!matches("title:ship", &n));
+        assert!(matches("folder:work", &n));
+        assert!(!matches("folder:wor", &n));
+        assert!(matches("color:red", &n));
+        assert!(matches("priority>=medium priority<urgent", &n));
+        assert!(!matches("priority:medium", &n));
+        assert!(matches("tag:overdue due<today due:yesterday", &n));
+        assert!(!matches("done<today", &n));
+        assert!(matches("owner:ana owner:", &n));
+        assert!(!matches("team:", &n));
+    }
+
+    #[test]
+    fn errors_name_the_term() {
+        assert_eq!(error(":value"), "Missing key in \":value\"");
+        assert_eq!(error("tag:later"), "Unknown tag \"later\"");
+        assert_eq!(error("color:teal"), "Unknown color \"teal\"");
+        assert_eq!(error("priority:huge"), "Unknown priority \"huge\"");
+        assert_eq!(error("due<someday"), "Could not read the date \"someday\"");
+        assert_eq!(error("title<plan"), "\"title\" can only be used with \":\"");
+        assert_eq!(error("owner>=ana"), "\"owner\" can only be used with \":\"");
+        assert_eq!(error("\"open"), "Unclosed quote");
+    }
+}
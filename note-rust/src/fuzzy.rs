@@ -0,0 +1,187 @@
+//! fzf-style fuzzy matching for the note finder.
+//!
+//! The query's characters must appear in order, ignoring case. Matches at
+//! the start of words and runs of consecutive characters score higher, and
+//! gaps between matched characters cost a little.
+
+use crate::NoteFormat;
+
+/// A ranked result: the note, its score and the matched character
+/// positions in its title and body.
+pub struct Hit {
+    pub index: usize,
+    pub score: i64,
+    pub title: Vec<usize>,
+    pub body: Vec<usize>,
+}
+
+/// Score of `query` against `text` and the positions (in chars) it matched,
+/// or `None` if the characters don't all appear in order.
+pub fn score(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let text: Vec<char> = text.chars().collect();
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some((0, vec![]));
+    }
+    // the shortest window ending at the leftmost complete match: scan
+    // forward to find the end, then backward to pull the start in
+    let mut qi = 0;
+    let mut end = None;
+    for (i, &c) in text.iter().enumerate() {
+        if same(c, query[qi]) {
+            qi += 1;
+            if qi == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    let mut positions = Vec::with_capacity(query.len());
+    let mut qi = query.len();
+    for i in (0..=end).rev() {
+        if qi > 0 && same(text[i], query[qi - 1]) {
+            qi -= 1;
+            positions.push(i);
+        }
+    }
+    positions.reverse();
+
+    let mut score = 0;
+    for (n, &pos) in positions.iter().enumerate() {
+        score += 16;
+        if pos == 0 || !text[pos - 1].is_alphanumeric() {
+            score += 10;
+        }
+        if n > 0 {
+            let gap = pos - positions[n - 1] - 1;
+            if gap == 0 {
+                score += 15;
+            } else {
+                score -= gap.min(10) as i64;
+            }
+        }
+    }
+    Some((score, positions))
+}
+
+fn same(a: char, b: char) -> bool {
+    a.to_lowercase().eq(b.to_lowercase())
+}
+
+/// Notes among `indices` matching `query` in their title or body, best
+/// first. Title matches are preferred over equally good body matches.
+pub fn rank(notes: &[NoteFormat], indices: &[usize], query: &str) -> Vec<Hit> {
+    let mut hits: Vec<Hit> = indices
+        .iter()
+        .filter_map(|&index| {
+            let note = &notes[index];
+            let title = score(query, &note.text);
+            let body = score(query, &note.body);
+            let (score, title, body) = match (title, body) {
+                (Some((t, tp)), Some((b, _))) if t + 8 >= b => (t + 8, tp, vec![]),
+                (_, Some((b, bp))) => (b, vec![], bp),
+                (Some((t, tp)), None) => (t + 8, tp, vec![]),
+                (None, None) => return None,
+            };
+            Some(Hit {
+                index,
+                score,
+                title,
+                body,
+            })
+        })
+        .collect();
+    hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(text: &str, body: &str) -> NoteFormat {
+        NoteFormat {
+            text: text.to_string(),
+            body: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn positions(query: &str, text: &str) -> Option<Vec<usize>> {
+        score(query, text).map(|(_, positions)| positions)
+    }
+
+    #[test]
+    fn characters_must_appear_in_order() {
+        assert_eq!(positions("nt", "note"), Some(vec![0, 2]));
+        assert_eq!(positions("NO", "note"), Some(vec![0, 1]));
+        assert_eq!(positions("n t", "note"), Some(vec![0, 2]));
+        assert_eq!(positions("tn", "note"), None);
+        assert_eq!(positions("notes", "note"), None);
+        assert_eq!(score("", "note"), Some((0, vec![])));
+        assert_eq!(score("  ", ""), Some((0, vec![])));
+    }
+
+    #[test]
+    fn positions_are_chars_of_the_shortest_window() {
+        // the `a` right before the `b`, not the first one
+        assert_eq!(positions("ab", "a xab"), Some(vec![3, 4]));
+        assert_eq!(positions("é", "café"), Some(vec![3]));
+        assert_eq!(positions("cé", "ça café"), Some(vec![3, 6]));
+    }
+
+    #[test]
+    fn word_starts_and_runs_score_higher() {
+        let score = |query, text| score(query, text).unwrap().0;
+        assert_eq!(score("no", "note"), 16 + 10 + 16 + 15);
+        assert_eq!(score("nt", "note"), 16 + 10 + 16 - 1);
+        assert!(score("b", "a b") > score("b", "ab"));
+        assert!(score("ab", "ab") > score("ab", "a-b"));
+        // gaps cost at most 10
+        assert_eq!(
+            score("ab", &format!("a{}b", "x".repeat(10))),
+            score("ab", &format!("a{}b", "x".repeat(40)))
+        );
+    }
+
+    #[test]
+    fn rank_puts_the_best_first() {
+        let notes = [
+            note("groceries", "milk"),
+            note("milk run", ""),
+            note("other", "buy milk"),
+            note("nothing", "here"),
+            note("m-i-l-k", "milk"),
+        ];
+        let hits = rank(&notes, &[0, 1, 2, 3, 4], "milk");
+        let order: Vec<usize> = hits.iter().map(|hit| hit.index).collect();
+        // a title match wins a tie, and equal scores keep the list order
+        assert_eq!(order, [1, 0, 2, 4]);
+        assert_eq!(hits[0].title, [0, 1, 2, 3]);
+        assert!(hits[0].body.is_empty());
+        assert!(hits[1].title.is_empty());
+        assert_eq!(hits[1].body, [0, 1, 2, 3]);
+        assert_eq!(hits[2].body, [4, 5, 6, 7]);
+        // the body matches better than the scattered title
+        assert!(hits[3].title.is_empty());
+        assert_eq!(hits[3].body, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn rank_prefers_the_title_over_an_equal_body() {
+        let hits = rank(&[note("milk", "milk")], &[0], "milk");
+        assert_eq!(hits[0].title, [0, 1, 2, 3]);
+        assert!(hits[0].body.is_empty());
+        assert_eq!(hits[0].score, score("milk", "milk").unwrap().0 + 8);
+    }
+
+    #[test]
+    fn rank_only_looks_at_the_given_notes() {
+        let notes = [note("milk", ""), note("bread", ""), note("milk", "")];
+        let hits = rank(&notes, &[1, 2], "milk");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].index, 2);
+        assert!(rank(&notes, &[], "milk").is_empty());
+    }
+}
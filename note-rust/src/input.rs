@@ -0,0 +1,508 @@
+//! The single-line text field used by every popup.
+//!
+//! The cursor moves and deletes by grapheme, so an accented letter or an
+//! emoji is one step. In a box one row high the field scrolls sideways to
+//! keep the cursor in view when the text is wider than the box; in a taller
+//! one the text wraps and scrolls by rows. Keys follow the shell:
+//!
+//! - Left/Right, Home/End (or Ctrl-A/Ctrl-E) move the cursor
+//! - Ctrl-Left/Ctrl-Right move by word
+//! - Backspace/Delete delete a character, Ctrl-W (or Alt-Backspace) the
+//!   word before the cursor
+//! - Ctrl-U deletes to the start of the line, Ctrl-K to the end
+//!
+//! With the vim keymap the field starts in insert mode, where the keys
+//! above work too. Esc switches to normal mode, where `h`/`l`, `0`/`$`,
+//! `w`/`b`/`e`, `x`, `D`, `C`, `S`, `dd`, `cc` and `d`/`c` with a motion
+//! edit the text and `i`/`a`/`I`/`A` go back to insert mode; Esc there
+//! closes the popup.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::{Position, Rect},
+    text::Line,
+    widgets::{Block, Clear, Paragraph},
+    Frame,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+static VIM_MODES: AtomicBool = AtomicBool::new(false);
+
+/// Turns insert and normal modes on for every field, for the vim keymap.
+pub fn use_vim_modes(on: bool) {
+    VIM_MODES.store(on, Ordering::Relaxed);
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TextInput {
+    value: String,
+    /// Byte offset of the cursor, always on a grapheme boundary.
+    cursor: usize,
+    /// Display column of the first visible character, or the first visible
+    /// row when the text wraps.
+    scroll: usize,
+    /// In vim normal mode rather than insert mode.
+    normal: bool,
+    /// `d` or `c` typed in normal mode, waiting for its motion.
+    operator: Option<char>,
+}
+
+impl TextInput {
+    /// A field holding `value` with the cursor at the end, for editing an
+    /// existing value.
+    pub fn new(value: impl Into<String>) -> TextInput {
+        let value = value.into();
+        TextInput {
+            cursor: value.len(),
+            value,
+            ..Default::default()
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        *self = TextInput::default();
+    }
+
+    /// Applies an editing key. Returns false for keys the field doesn't
+    /// use, such as Enter or Esc, so the popup can handle them.
+    pub fn handle(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        if VIM_MODES.load(Ordering::Relaxed) && !ctrl && !alt {
+            if self.normal {
+                return self.handle_normal(key.code);
+            }
+            if key.code == KeyCode::Esc {
+                self.normal = true;
+                self.cursor = self.prev_boundary();
+                return true;
+            }
+        }
+        match key.code {
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.value.len(),
+            KeyCode::Char('w') if ctrl => self.delete_to(self.word_start()),
+            KeyCode::Char('u') if ctrl => self.delete_to(0),
+            KeyCode::Char('k') if ctrl => self.delete_to(self.value.len()),
+            KeyCode::Char(c) if !ctrl && !alt => {
+                self.value.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            KeyCode::Backspace if alt => self.delete_to(self.word_start()),
+            KeyCode::Backspace => self.delete_to(self.prev_boundary()),
+            KeyCode::Delete => self.delete_to(self.next_boundary()),
+            KeyCode::Left if ctrl => self.cursor = self.word_start(),
+            KeyCode::Right if ctrl => self.cursor = self.word_end(),
+            KeyCode::Left => self.cursor = self.prev_boundary(),
+            KeyCode::Right => self.cursor = self.next_boundary(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.value.len(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// A key in vim normal mode. Other characters are swallowed, so they
+    /// don't reach the popup, while Esc, Enter and keys such as Tab fall
+    /// through to it.
+    fn handle_normal(&mut self, code: KeyCode) -> bool {
+        if let Some(operator) = self.operator.take() {
+            let to = match code {
+                KeyCode::Char(c) if c == operator => {
+                    self.cursor = 0;
+                    Some(self.value.len())
+                }
+                KeyCode::Char('w') if operator == 'c' => Some(self.word_end()),
+                KeyCode::Char('w') => Some(self.next_word_start()),
+                KeyCode::Char('e') => Some(self.word_end()),
+                KeyCode::Char('b') => Some(self.word_start()),
+                KeyCode::Char('0') => Some(0),
+                KeyCode::Char('$') => Some(self.value.len()),
+                _ => None,
+            };
+            if let Some(to) = to {
+                self.delete_to(to);
+                self.normal = operator == 'd';
+            }
+            return true;
+        }
+        match code {
+            KeyCode::Char('i') => self.normal = false,
+            KeyCode::Char('a') => {
+                self.cursor = self.next_boundary();
+                self.normal = false;
+            }
+            KeyCode::Char('I') => {
+                self.cursor = 0;
+                self.normal = false;
+            }
+            KeyCode::Char('A') => {
+                self.cursor = self.value.len();
+                self.normal = false;
+            }
+            KeyCode::Char('h') | KeyCode::Left | KeyCode::Backspace => {
+                self.cursor = self.prev_boundary()
+            }
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Char(' ') => {
+                if self.next_boundary() < self.value.len() {
+                    self.cursor = self.next_boundary();
+                }
+            }
+            KeyCode::Char('0') | KeyCode::Char('^') | KeyCode::Home => self.cursor = 0,
+            KeyCode::Char('$') | KeyCode::End => self.cursor = self.prev_end(),
+            KeyCode::Char('w') => self.cursor = self.next_word_start(),
+            KeyCode::Char('b') => self.cursor = self.word_start(),
+            KeyCode::Char('e') => {
+                self.cursor = self.next_boundary();
+                self.cursor = self.word_end();
+                self.cursor = self.prev_boundary();
+            }
+            KeyCode::Char('x') | KeyCode::Delete => self.delete_to(self.next_boundary()),
+            KeyCode::Char('X') => self.delete_to(self.prev_boundary()),
+            KeyCode::Char('D') => self.delete_to(self.value.len()),
+            KeyCode::Char('C') => {
+                self.delete_to(self.value.len());
+                self.normal = false;
+            }
+            KeyCode::Char('S') => {
+                self.value.clear();
+                self.cursor = 0;
+                self.normal = false;
+            }
+            KeyCode::Char(c @ ('d' | 'c')) => self.operator = Some(c),
+            KeyCode::Char(_) => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// Draws the field in `area` inside `block`, over whatever was there,
+    /// and puts the terminal cursor at the insertion point.
+    pub fn render(&mut self, f: &mut Frame, area: Rect, block: Block) {
+        self.render_prefixed(f, area, block, "");
+    }
+
+    /// Like `render`, with `prefix` (a prompt such as `> `) shown before
+    /// the text and never scrolled away.
+    pub fn render_prefixed(&mut self, f: &mut Frame, area: Rect, block: Block, prefix: &str) {
+        let inner = block.inner(area);
+        // the mode goes on the bottom border, when there is one
+        let block = if VIM_MODES.load(Ordering::Relaxed) && inner.bottom() < area.bottom() {
+            let mode = match (self.normal, self.operator) {
+                (true, Some(operator)) => format!(" NORMAL {} ", operator),
+                (true, None) => " NORMAL ".to_string(),
+                (false, _) => " INSERT ".to_string(),
+            };
+            block.title_bottom(Line::from(mode).left_aligned())
+        } else {
+            block
+        };
+        if inner.height > 1 {
+            let (rows, (row, column)) = self.wrapped(prefix, inner.width as usize);
+            let height = inner.height as usize;
+            self.scroll = self.scroll.min(row).max((row + 1).saturating_sub(height));
+            let lines: Vec<Line> = rows
+                .into_iter()
+                .skip(self.scroll)
+                .take(height)
+                .map(Line::from)
+                .collect();
+            f.render_widget(Clear, area);
+            f.render_widget(Paragraph::new(lines).block(block), area);
+            f.set_cursor_position(Position::new(
+                (inner.x as usize + column).min(inner.right().saturating_sub(1) as usize) as u16,
+                inner.y + (row - self.scroll) as u16,
+            ));
+            return;
+        }
+
+        let width = (inner.width as usize).saturating_sub(prefix.width());
+        let before = self.value[..self.cursor].width();
+        // pull back when the text got shorter, then follow the cursor
+        self.scroll = self
+            .scroll
+            .min((self.value.width() + 1).saturating_sub(width))
+            .min(before);
+        if width > 0 && before >= self.scroll + width {
+            self.scroll = before + 1 - width;
+        }
+
+        // skip whole graphemes, so a wide one is never cut in half
+        let mut start = self.value.len();
+        let mut column = 0;
+        for (i, grapheme) in self.value.grapheme_indices(true) {
+            if column >= self.scroll {
+                start = i;
+                break;
+            }
+            column += grapheme.width();
+        }
+        let visible = format!("{}{}", prefix, &self.value[start..]);
+        f.render_widget(Clear, area);
+        f.render_widget(Paragraph::new(visible).block(block), area);
+
+        let x = inner.x as usize + prefix.width() + before.saturating_sub(column);
+        f.set_cursor_position(Position::new(
+            (x as u16).min(inner.right().saturating_sub(1)),
+            inner.y,
+        ));
+    }
+
+    /// `prefix` and the value broken into rows of `width` columns, and the
+    /// row and column of the cursor. A grapheme that doesn't fit at the end
+    /// of a row starts the next one.
+    fn wrapped(&self, prefix: &str, width: usize) -> (Vec<String>, (usize, usize)) {
+        let mut rows = vec![prefix.to_string()];
+        let mut column = prefix.width();
+        let mut cursor = None;
+        for (i, grapheme) in self.value.grapheme_indices(true) {
+            if column > 0 && column + grapheme.width() > width {
+                rows.push(String::new());
+                column = 0;
+            }
+            if i == self.cursor {
+                cursor = Some((rows.len() - 1, column));
+            }
+            rows.last_mut().unwrap().push_str(grapheme);
+            column += grapheme.width();
+        }
+        let cursor = cursor.unwrap_or_else(|| {
+            // past the end, which may be the start of a new row
+            if column >= width && column > 0 {
+                rows.push(String::new());
+                column = 0;
+            }
+            (rows.len() - 1, column)
+        });
+        (rows, cursor)
+    }
+
+    fn delete_to(&mut self, to: usize) {
+        if to < self.cursor {
+            self.value.replace_range(to..self.cursor, "");
+            self.cursor = to;
+        } else {
+            self.value.replace_range(self.cursor..to, "");
+        }
+    }
+
+    fn prev_boundary(&self) -> usize {
+        self.value[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> usize {
+        self.value[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map_or(self.cursor, |g| self.cursor + g.len())
+    }
+
+    /// Start of the word before the cursor, skipping whitespace first.
+    fn word_start(&self) -> usize {
+        let before = self.value[..self.cursor].trim_end();
+        before
+            .grapheme_indices(true)
+            .rev()
+            .find(|(_, g)| g.chars().all(char::is_whitespace))
+            .map_or(0, |(i, g)| i + g.len())
+    }
+
+    /// Start of the last grapheme, where `$` puts the cursor.
+    fn prev_end(&self) -> usize {
+        self.value
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    /// Start of the next word, past the rest of this one and the
+    /// whitespace after it.
+    fn next_word_start(&self) -> usize {
+        let after = &self.value[self.cursor..];
+        let word = after
+            .grapheme_indices(true)
+            .find(|(_, g)| g.chars().all(char::is_whitespace))
+            .map_or(after.len(), |(i, _)| i);
+        let rest = &after[word..];
+        self.cursor + word + (rest.len() - rest.trim_start().len())
+    }
+
+    /// End of the word after the cursor, skipping whitespace first.
+    fn word_end(&self) -> usize {
+        let after = &self.value[self.cursor..];
+        let skipped = after.len() - after.trim_start().len();
+        after[skipped..]
+            .grapheme_indices(true)
+            .find(|(_, g)| g.chars().all(char::is_whitespace))
+            .map_or(self.value.len(), |(i, _)| self.cursor + skipped + i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::CONTROL)
+    }
+
+    fn press(input: &mut TextInput, keys: &[KeyEvent]) {
+        for &key in keys {
+            input.handle(key);
+        }
+    }
+
+    /// Keys in vim normal mode, without turning the modes on for the other
+    /// tests running at the same time.
+    fn normal(input: &mut TextInput, keys: &str) {
+        input.normal = true;
+        for c in keys.chars() {
+            input.handle_normal(KeyCode::Char(c));
+        }
+    }
+
+    #[test]
+    fn graphemes_are_one_step() {
+        // e and a combining accent, a thumbs up with a skin tone, a wide character
+        let mut input = TextInput::new("ae\u{301}👍🏽漢");
+        press(&mut input, &[key(KeyCode::Left), key(KeyCode::Backspace)]);
+        assert_eq!(input.value(), "ae\u{301}漢");
+        press(&mut input, &[key(KeyCode::Backspace)]);
+        assert_eq!(input.value(), "a漢");
+        press(&mut input, &[key(KeyCode::Delete)]);
+        assert_eq!(input.value(), "a");
+        press(
+            &mut input,
+            &[key(KeyCode::Char('é')), key(KeyCode::Char('字'))],
+        );
+        assert_eq!(input.value(), "aé字");
+        press(&mut input, &[key(KeyCode::Left), key(KeyCode::Char('-'))]);
+        assert_eq!(input.value(), "aé-字");
+    }
+
+    #[test]
+    fn word_motions() {
+        let mut input = TextInput::new("one two  three");
+        press(&mut input, &[ctrl(KeyCode::Left)]);
+        assert_eq!(input.cursor, 9);
+        press(&mut input, &[ctrl(KeyCode::Left)]);
+        assert_eq!(input.cursor, 4);
+        press(&mut input, &[ctrl(KeyCode::Right)]);
+        assert_eq!(input.cursor, 7);
+        press(&mut input, &[ctrl(KeyCode::Right)]);
+        assert_eq!(input.cursor, 14);
+        press(&mut input, &[ctrl(KeyCode::Char('w'))]);
+        assert_eq!(input.value(), "one two  ");
+        press(
+            &mut input,
+            &[KeyEvent::new(KeyCode::Backspace, KeyModifiers::ALT)],
+        );
+        assert_eq!(input.value(), "one ");
+        press(&mut input, &[ctrl(KeyCode::Char('w'))]);
+        assert_eq!(input.value(), "");
+    }
+
+    #[test]
+    fn deleting_at_the_ends() {
+        let mut input = TextInput::new("abc");
+        press(
+            &mut input,
+            &[key(KeyCode::Delete), ctrl(KeyCode::Char('k'))],
+        );
+        assert_eq!((input.value(), input.cursor), ("abc", 3));
+        press(&mut input, &[key(KeyCode::Home), key(KeyCode::Backspace)]);
+        press(
+            &mut input,
+            &[ctrl(KeyCode::Char('u')), ctrl(KeyCode::Char('w'))],
+        );
+        assert_eq!((input.value(), input.cursor), ("abc", 0));
+        press(&mut input, &[key(KeyCode::Delete)]);
+        assert_eq!((input.value(), input.cursor), ("bc", 0));
+        press(
+            &mut input,
+            &[ctrl(KeyCode::Char('e')), key(KeyCode::Backspace)],
+        );
+        assert_eq!((input.value(), input.cursor), ("b", 1));
+        press(&mut input, &[key(KeyCode::Left), ctrl(KeyCode::Char('k'))]);
+        assert_eq!((input.value(), input.cursor), ("", 0));
+        press(&mut input, &[key(KeyCode::Backspace), key(KeyCode::Delete)]);
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn vim_normal_mode() {
+        let mut input = TextInput::new("one two three");
+        normal(&mut input, "0w");
+        assert_eq!(input.cursor, 4);
+        normal(&mut input, "e");
+        assert_eq!(input.cursor, 6);
+        normal(&mut input, "bdw");
+        assert_eq!((input.value(), input.cursor), ("one three", 4));
+        normal(&mut input, "$x");
+        assert_eq!(input.value(), "one thre");
+        normal(&mut input, "0D");
+        assert_eq!(input.value(), "");
+        let mut input = TextInput::new("one two");
+        normal(&mut input, "0cw");
+        assert_eq!(input.value(), " two");
+        assert!(!input.normal);
+        normal(&mut input, "cc");
+        assert_eq!((input.value(), input.normal), ("", false));
+    }
+
+    #[test]
+    fn text_wraps_by_width() {
+        let rows = |value: &str, prefix: &str, width| TextInput::new(value).wrapped(prefix, width);
+        assert_eq!(
+            rows("abcdef", "", 4),
+            (vec!["abcd".into(), "ef".into()], (1, 2))
+        );
+        // the cursor after a full row starts the next one
+        assert_eq!(
+            rows("abcd", "", 4),
+            (vec!["abcd".into(), "".into()], (1, 0))
+        );
+        // a wide character that doesn't fit moves down whole
+        assert_eq!(
+            rows("ab漢c", "", 3),
+            (vec!["ab".into(), "漢c".into(), "".into()], (2, 0))
+        );
+        assert_eq!(
+            rows("abc", "> ", 4),
+            (vec!["> ab".into(), "c".into()], (1, 1))
+        );
+        assert_eq!(rows("", "", 4), (vec!["".into()], (0, 0)));
+    }
+
+    #[test]
+    fn render_wraps_in_a_tall_box() {
+        let mut terminal = Terminal::new(TestBackend::new(10, 4)).unwrap();
+        let mut input = TextInput::new("abcdefghijkl");
+        terminal
+            .draw(|f| input.render(f, Rect::new(0, 0, 10, 4), Block::bordered()))
+            .unwrap();
+        let buffer = terminal.backend().buffer().clone();
+        let row = |y| (1..9).map(|x| buffer[(x, y)].symbol()).collect::<String>();
+        assert_eq!(row(1), "abcdefgh");
+        assert_eq!(row(2), "ijkl    ");
+        assert_eq!(terminal.get_cursor_position().unwrap(), Position::new(5, 2));
+    }
+}
@@ -0,0 +1,63 @@
+//! Daily journal notes: one note per day, titled with the date.
+//!
+//! The title format is a `chrono` format string taken from
+//! `NOTE_JOURNAL_FORMAT` (default `%Y-%m-%d`). New entries take their body
+//! from the template named in `NOTE_JOURNAL_TEMPLATE`, if set.
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::template::{self, Template};
+use crate::NoteFormat;
+
+pub fn date_format() -> String {
+    std::env::var("NOTE_JOURNAL_FORMAT")
+        .ok()
+        .filter(|format| !format.is_empty())
+        .unwrap_or_else(|| "%Y-%m-%d".to_string())
+}
+
+pub fn title(date: NaiveDate, format: &str) -> String {
+    date.format(format).to_string()
+}
+
+/// The day a journal note is for, if `note` is one.
+pub fn date_of(note: &NoteFormat, format: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(note.text.trim(), format).ok()
+}
+
+/// Index of the journal note for `date`.
+pub fn find(notes: &[NoteFormat], date: NaiveDate, format: &str) -> Option<usize> {
+    let title = title(date, format);
+    notes.iter().position(|note| note.text.trim() == title)
+}
+
+/// The closest journal note before (`forward == false`) or after `date`.
+pub fn step(notes: &[NoteFormat], date: NaiveDate, forward: bool, format: &str) -> Option<usize> {
+    let entries = notes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, note)| Some((date_of(note, format)?, i)));
+    if forward {
+        entries.filter(|(d, _)| *d > date).min().map(|(_, i)| i)
+    } else {
+        entries.filter(|(d, _)| *d < date).max().map(|(_, i)| i)
+    }
+}
+
+/// Title and body for a new journal note for `date`. `template_name`
+/// overrides `NOTE_JOURNAL_TEMPLATE`; only the template's body is used.
+pub fn new_entry(
+    date: NaiveDate,
+    now: NaiveDateTime,
+    format: &str,
+    templates: &[Template],
+    template_name: Option<&str>,
+) -> (String, String) {
+    let from_env = std::env::var("NOTE_JOURNAL_TEMPLATE").ok();
+    let body = template_name
+        .or(from_env.as_deref())
+        .and_then(|name| template::find(templates, name))
+        .map(|template| template.expand(now).1)
+        .unwrap_or_default();
+    (title(date, format), body)
+}
@@ -0,0 +1,1013 @@
+//! Key bindings of the main screen.
+//!
+//! Every key does a named [`Action`]. A keymap binds sequences of one or
+//! more keys to actions, so vim-style bindings such as `gg` or `dd` work.
+//! In the vim keymap, digits typed first are a count (`5j`) whenever they
+//! aren't bound themselves. Two keymaps are built in, chosen with
+//! `NOTE_KEYMAP`:
+//!
+//! - `default`: single keys, as listed in the README
+//! - `vim`: counts, `gg`/`G`, Ctrl-D/Ctrl-U, `dd`, `yy`/`p`, `/` to search,
+//!   and insert/normal modes in the text fields of the popups
+//!
+//! A `keymap.conf` next to the notes (or the file in `NOTE_KEYMAP_FILE`)
+//! changes bindings on top of the built-in keymap, one per line: the keys,
+//! separated by spaces, then the action or `none` to unbind them.
+//!
+//! ```text
+//! # comments start with #
+//! Ctrl-n    down
+//! g d       due
+//! x         delete
+//! d d       none
+//! ```
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Add,
+    Edit,
+    EditSelected,
+    Delete,
+    DeleteNow,
+    Yank,
+    Paste,
+    Undo,
+    Redo,
+    Done,
+    Priority,
+    Pin,
+    Archive,
+    ShowNotes,
+    ShowArchive,
+    Sort,
+    Folders,
+    Collapse,
+    Expand,
+    ToggleFolder,
+    Move,
+    Due,
+    Repeat,
+    Agenda,
+    OpenLink,
+    Back,
+    Forward,
+    Fields,
+    Filter,
+    Today,
+    PrevDay,
+    NextDay,
+    Timer,
+    Label,
+    Review,
+    Attachments,
+    Find,
+    NextMatch,
+    PrevMatch,
+    Jump,
+    Down,
+    Up,
+    Top,
+    Bottom,
+    HalfPageDown,
+    HalfPageUp,
+    Quit,
+    Escape,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 49] = {
+        use Action::*;
+        [
+            Add,
+            Edit,
+            EditSelected,
+            Delete,
+            DeleteNow,
+            Yank,
+            Paste,
+            Undo,
+            Redo,
+            Done,
+            Priority,
+            Pin,
+            Archive,
+            ShowNotes,
+            ShowArchive,
+            Sort,
+            Folders,
+            Collapse,
+            Expand,
+            ToggleFolder,
+            Move,
+            Due,
+            Repeat,
+            Agenda,
+            OpenLink,
+            Back,
+            Forward,
+            Fields,
+            Filter,
+            Today,
+            PrevDay,
+            NextDay,
+            Timer,
+            Label,
+            Review,
+            Attachments,
+            Find,
+            NextMatch,
+            PrevMatch,
+            Jump,
+            Down,
+            Up,
+            Top,
+            Bottom,
+            HalfPageDown,
+            HalfPageUp,
+            Quit,
+            Escape,
+            Help,
+        ]
+    };
+
+    /// The name used in `keymap.conf`.
+    pub fn name(self) -> &'static str {
+        use Action::*;
+        match self {
+            Add => "add",
+            Edit => "edit",
+            EditSelected => "edit-selected",
+            Delete => "delete",
+            DeleteNow => "delete-now",
+            Yank => "yank",
+            Paste => "paste",
+            Undo => "undo",
+            Redo => "redo",
+            Done => "done",
+            Priority => "priority",
+            Pin => "pin",
+            Archive => "archive",
+            ShowNotes => "show-notes",
+            ShowArchive => "show-archive",
+            Sort => "sort",
+            Folders => "folders",
+            Collapse => "collapse",
+            Expand => "expand",
+            ToggleFolder => "toggle-folder",
+            Move => "move",
+            Due => "due",
+            Repeat => "repeat",
+            Agenda => "agenda",
+            OpenLink => "open-link",
+            Back => "back",
+            Forward => "forward",
+            Fields => "fields",
+            Filter => "filter",
+            Today => "today",
+            PrevDay => "prev-day",
+            NextDay => "next-day",
+            Timer => "timer",
+            Label => "label",
+            Review => "review",
+            Attachments => "attachments",
+            Find => "find",
+            NextMatch => "next-match",
+            PrevMatch => "prev-match",
+            Jump => "jump",
+            Down => "down",
+            Up => "up",
+            Top => "top",
+            Bottom => "bottom",
+            HalfPageDown => "half-page-down",
+            HalfPageUp => "half-page-up",
+            Quit => "quit",
+            Escape => "escape",
+            Help => "help",
+        }
+    }
+
+    /// What the action does, for the footer when it isn't listed with
+    /// the others of its entry.
+    fn label(self) -> &'static str {
+        use Action::*;
+        match self {
+            Add => "add note",
+            Edit => "edit command",
+            EditSelected => "edit selected note",
+            Delete => "delete",
+            DeleteNow => "delete without asking",
+            Yank => "yank note",
+            Paste => "paste note",
+            Undo => "undo",
+            Redo => "redo",
+            Done => "done",
+            Priority => "priority",
+            Pin => "pin",
+            Archive => "archive",
+            ShowNotes => "notes panel",
+            ShowArchive => "archive panel",
+            Sort => "sort",
+            Folders => "folders",
+            Collapse => "collapse folder",
+            Expand => "expand folder",
+            ToggleFolder => "toggle folder",
+            Move => "move",
+            Due => "due date",
+            Repeat => "repeat",
+            Agenda => "agenda",
+            OpenLink => "open link",
+            Back => "back",
+            Forward => "forward",
+            Fields => "fields",
+            Filter => "filter",
+            Today => "today",
+            PrevDay => "prev day",
+            NextDay => "next day",
+            Timer => "timer",
+            Label => "label",
+            Review => "review",
+            Attachments => "attachments",
+            Find => "find",
+            NextMatch => "next match",
+            PrevMatch => "prev match",
+            Jump => "jump to note",
+            Down => "down",
+            Up => "up",
+            Top => "top",
+            Bottom => "bottom",
+            HalfPageDown => "half page down",
+            HalfPageUp => "half page up",
+            Quit => "quit",
+            Escape => "clear search",
+            Help => "all keys",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// The footer, in this order. When all the actions of an entry are bound
+/// they are listed together, as in `j/k : down/up`.
+const FOOTER: &[(&[Action], &str)] = {
+    use Action::*;
+    &[
+        (&[Down, Up], "down/up"),
+        (&[Top, Bottom], "top/bottom"),
+        (&[HalfPageDown, HalfPageUp], "half page down/up"),
+        (&[Quit], "quit"),
+        (&[Add], "add note"),
+        (&[Edit], "edit command"),
+        (&[EditSelected], "edit selected note"),
+        (&[Delete], "delete"),
+        (&[DeleteNow], "delete without asking"),
+        (&[Yank, Paste], "yank/paste note"),
+        (&[Undo, Redo], "undo/redo"),
+        (&[Done], "done"),
+        (&[Priority], "priority"),
+        (&[Pin], "pin"),
+        (&[Archive], "archive"),
+        (&[ShowNotes, ShowArchive], "notes/archive"),
+        (&[Sort], "sort"),
+        (&[Folders], "folders"),
+        (&[Collapse, Expand], "collapse/expand folder"),
+        (&[ToggleFolder], "toggle folder"),
+        (&[Move], "move"),
+        (&[Due], "due date"),
+        (&[Repeat], "repeat"),
+        (&[Agenda], "agenda"),
+        (&[OpenLink], "open link"),
+        (&[Back, Forward], "back/forward"),
+        (&[Fields], "fields"),
+        (&[Filter], "filter"),
+        (&[Today], "today"),
+        (&[PrevDay, NextDay], "prev/next day"),
+        (&[Timer], "timer"),
+        (&[Label], "label"),
+        (&[Review], "review"),
+        (&[Attachments], "attachments"),
+        (&[Find], "find"),
+        (&[NextMatch, PrevMatch], "next/prev match"),
+        (&[Jump], "jump to note"),
+        (&[Escape], "clear search"),
+        (&[Help], "all keys"),
+    ]
+};
+
+/// A key and the Ctrl/Alt modifiers held with it. Shift is part of the
+/// character, so `G` is Shift-g.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Chord {
+    const fn key(code: KeyCode) -> Chord {
+        Chord {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    const fn ctrl(c: char) -> Chord {
+        Chord {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::CONTROL,
+        }
+    }
+
+    pub fn from_event(key: KeyEvent) -> Chord {
+        let mut modifiers = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char(_) => {}
+            // some terminals leave Shift out, but there is no BackTab without it
+            KeyCode::BackTab => modifiers |= KeyModifiers::SHIFT,
+            _ => modifiers |= key.modifiers & KeyModifiers::SHIFT,
+        }
+        Chord {
+            code: key.code,
+            modifiers,
+        }
+    }
+
+    /// Reads a key as written in `keymap.conf` and shown in the footer:
+    /// `x`, `G`, `Ctrl-d`, `Alt-x`, `Shift-Tab`, `Space`, `PageDown`, `F5`.
+    pub fn parse(word: &str) -> Option<Chord> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = word;
+        'prefixes: while rest.chars().nth(1).is_some() {
+            for (prefix, modifier) in [
+                ("ctrl-", KeyModifiers::CONTROL),
+                ("alt-", KeyModifiers::ALT),
+                ("shift-", KeyModifiers::SHIFT),
+            ] {
+                if rest.len() > prefix.len()
+                    && rest.is_char_boundary(prefix.len())
+                    && rest[..prefix.len()].eq_ignore_ascii_case(prefix)
+                {
+                    modifiers |= modifier;
+                    rest = &rest[prefix.len()..];
+                    continue 'prefixes;
+                }
+            }
+            break;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next()?, chars.next()) {
+            (c, None) => KeyCode::Char(c),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                name => match name.strip_prefix('f')?.parse() {
+                    Ok(n @ 1..=12) => KeyCode::F(n),
+                    _ => return None,
+                },
+            },
+        };
+        // the terminal sends Shift-g as G, and Ctrl-d and Ctrl-D alike
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                if !c.is_ascii_alphabetic() {
+                    return None;
+                }
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            code => code,
+        };
+        Some(Chord { code, modifiers })
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) if self.modifiers.contains(KeyModifiers::CONTROL) => {
+                write!(f, "{}", c.to_ascii_uppercase())
+            }
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::BackTab => write!(f, "Tab"),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// The largest count, so a long run of digits can't overflow or make a
+/// move take forever.
+const MAX_COUNT: usize = 9999;
+
+/// Keys typed so far towards a binding, and the count typed before them.
+#[derive(Clone, Debug, Default)]
+pub struct Pending {
+    keys: Vec<Chord>,
+    count: Option<usize>,
+}
+
+impl Pending {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.count.is_none()
+    }
+}
+
+impl fmt::Display for Pending {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(count) = self.count {
+            write!(f, "{}", count)?;
+        }
+        for key in &self.keys {
+            write!(f, "{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a key press amounts to.
+pub enum Outcome {
+    /// A complete binding, with its count (1 when none was typed) and
+    /// whether a count was typed at all.
+    Action(Action, usize, bool),
+    /// Part of a longer binding or a count, or Esc cancelling one.
+    Pending,
+    Unbound,
+}
+
+pub struct Keymap {
+    bindings: Vec<(Vec<Chord>, Action)>,
+    /// Whether the text fields of the popups use insert and normal modes.
+    pub vim: bool,
+}
+
+/// Keys as the footer shows them and they are typed, such as `gg`.
+fn show(keys: &[Chord]) -> String {
+    keys.iter().map(|key| key.to_string()).collect()
+}
+
+/// Keys as they are written in `keymap.conf`, such as `g g`.
+fn spaced(keys: &[Chord]) -> String {
+    let words: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+    words.join(" ")
+}
+
+/// Where the keymap file is: `NOTE_KEYMAP_FILE`, or `keymap.conf` next
+/// to the notes. The second value says whether it was asked for, so it
+/// must exist.
+pub fn config_path(notes_path: &str) -> (PathBuf, bool) {
+    match std::env::var("NOTE_KEYMAP_FILE") {
+        Ok(file) if !file.is_empty() => (PathBuf::from(file), true),
+        _ => (
+            Path::new(notes_path)
+                .parent()
+                .unwrap_or(Path::new("."))
+                .join("keymap.conf"),
+            false,
+        ),
+    }
+}
+
+/// Bindings both keymaps share.
+fn common() -> Vec<(Vec<Chord>, Action)> {
+    use Action::*;
+    let key = |c: char| vec![Chord::key(KeyCode::Char(c))];
+    vec![
+        (key('q'), Quit),
+        (vec![Chord::key(KeyCode::Esc)], Escape),
+        (key('?'), Help),
+        (key('a'), Add),
+        (key('e'), Edit),
+        (vec![Chord::key(KeyCode::Enter)], EditSelected),
+        (key('u'), Undo),
+        (vec![Chord::ctrl('r')], Redo),
+        (key('c'), Done),
+        (key('P'), Pin),
+        (key('z'), Archive),
+        (key('s'), Sort),
+        (vec![Chord::key(KeyCode::Tab)], Folders),
+        (key('h'), Collapse),
+        (vec![Chord::key(KeyCode::Left)], Collapse),
+        (key('l'), Expand),
+        (vec![Chord::key(KeyCode::Right)], Expand),
+        (key(' '), ToggleFolder),
+        (key('m'), Move),
+        (key('D'), Due),
+        (key('R'), Repeat),
+        (key('A'), Agenda),
+        (key('o'), OpenLink),
+        (key('['), Back),
+        (key(']'), Forward),
+        (key('v'), Fields),
+        (key('F'), Filter),
+        (key('t'), Today),
+        (key('<'), PrevDay),
+        (key('>'), NextDay),
+        (key('T'), Timer),
+        (key('L'), Label),
+        (key('r'), Review),
+        (key('n'), NextMatch),
+        (key('N'), PrevMatch),
+        (key('j'), Down),
+        (vec![Chord::key(KeyCode::Down)], Down),
+        (key('k'), Up),
+        (vec![Chord::key(KeyCode::Up)], Up),
+        (vec![Chord::key(KeyCode::Home)], Top),
+        (vec![Chord::key(KeyCode::End)], Bottom),
+        (vec![Chord::key(KeyCode::PageDown)], HalfPageDown),
+        (vec![Chord::key(KeyCode::PageUp)], HalfPageUp),
+    ]
+}
+
+impl Keymap {
+    pub fn default_keys() -> Keymap {
+        use Action::*;
+        let mut bindings = common();
+        bindings.extend([
+            (vec![Chord::key(KeyCode::Char('d'))], Delete),
+            (vec![Chord::key(KeyCode::Char('p'))], Priority),
+            (vec![Chord::key(KeyCode::Char('1'))], ShowNotes),
+            (vec![Chord::key(KeyCode::Char('2'))], ShowArchive),
+            (vec![Chord::key(KeyCode::Char('y'))], Attachments),
+            (vec![Chord::key(KeyCode::Char('f'))], Find),
+            (vec![Chord::key(KeyCode::Char('/'))], Jump),
+        ]);
+        Keymap {
+            bindings,
+            vim: false,
+        }
+    }
+
+    pub fn vim() -> Keymap {
+        use Action::*;
+        let keys = |s: &str| s.chars().map(|c| Chord::key(KeyCode::Char(c))).collect();
+        // before the shared ones, so the footer shows gg rather than Home
+        let mut bindings = vec![
+            (keys("gg"), Top),
+            (keys("G"), Bottom),
+            (vec![Chord::ctrl('d')], HalfPageDown),
+            (vec![Chord::ctrl('u')], HalfPageUp),
+            (keys("dd"), DeleteNow),
+            (keys("yy"), Yank),
+            (keys("p"), Paste),
+            (keys("+"), Priority),
+            (keys("g1"), ShowNotes),
+            (keys("g2"), ShowArchive),
+            (keys("ga"), Attachments),
+            (keys("/"), Find),
+            (vec![Chord::ctrl('p')], Jump),
+        ];
+        bindings.extend(common());
+        Keymap {
+            bindings,
+            vim: true,
+        }
+    }
+
+    /// The keymap named by `NOTE_KEYMAP`, the default one if it is unset.
+    pub fn from_env() -> Result<Keymap, String> {
+        match std::env::var("NOTE_KEYMAP").as_deref() {
+            Err(_) | Ok("") | Ok("default") => Ok(Keymap::default_keys()),
+            Ok("vim") => Ok(Keymap::vim()),
+            Ok(other) => Err(format!(
+                "Unknown keymap \"{}\" in NOTE_KEYMAP (default or vim)",
+                other
+            )),
+        }
+    }
+
+    /// The keymap named by `NOTE_KEYMAP` with the changes in the keymap
+    /// file, or every problem found in them, one per line of the file.
+    pub fn load(notes_path: &str) -> Result<Keymap, Vec<String>> {
+        let mut keymap = Keymap::from_env().map_err(|err| vec![err])?;
+        let (path, required) = config_path(notes_path);
+        match fs::read_to_string(&path) {
+            Ok(text) => keymap.configure(&text).map_err(|errors| {
+                errors
+                    .into_iter()
+                    .map(|(line, err)| format!("{}:{}: {}", path.display(), line, err))
+                    .collect::<Vec<_>>()
+            })?,
+            Err(err) if err.kind() == ErrorKind::NotFound && !required => {}
+            Err(err) => return Err(vec![format!("{}: {}", path.display(), err)]),
+        }
+        Ok(keymap)
+    }
+
+    /// Applies the lines of a keymap file. A binding replaces the one on
+    /// the same keys; errors come with their line number.
+    fn configure(&mut self, text: &str) -> Result<(), Vec<(usize, String)>> {
+        let mut errors = vec![];
+        // keys from the file, with their action (none to unbind) and line
+        let mut lines: Vec<(Vec<Chord>, Option<Action>, usize)> = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let n = n + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let Some((&name, keys)) = words.split_last().filter(|(_, keys)| !keys.is_empty())
+            else {
+                errors.push((
+                    n,
+                    "Expected keys and an action, like \"Ctrl-n down\"".into(),
+                ));
+                continue;
+            };
+            let action = match name {
+                "none" => None,
+                name => match Action::from_name(name) {
+                    Some(action) => Some(action),
+                    None => {
+                        errors.push((n, format!("Unknown action \"{}\"", name)));
+                        continue;
+                    }
+                },
+            };
+            if let Some(word) = keys.iter().find(|word| Chord::parse(word).is_none()) {
+                errors.push((
+                    n,
+                    format!(
+                        "Unknown key \"{}\" (separate keys with spaces, as in \"g g\")",
+                        word
+                    ),
+                ));
+                continue;
+            }
+            let keys: Vec<Chord> = keys.iter().filter_map(|word| Chord::parse(word)).collect();
+            if let Some((_, _, first)) = lines.iter().find(|(bound, _, _)| *bound == keys) {
+                errors.push((
+                    n,
+                    format!("\"{}\" is already bound on line {}", spaced(&keys), first),
+                ));
+                continue;
+            }
+            lines.push((keys, action, n));
+        }
+
+        self.bindings
+            .retain(|(keys, _)| !lines.iter().any(|(bound, _, _)| bound == keys));
+        let ours: Vec<(Vec<Chord>, Action, usize)> = lines
+            .into_iter()
+            .filter_map(|(keys, action, n)| Some((keys, action?, n)))
+            .collect();
+        // the shorter of two bindings where one starts with the other
+        // always wins, so the longer one could never be typed
+        for (i, (keys, action, n)) in ours.iter().enumerate() {
+            let others = self
+                .bindings
+                .iter()
+                .map(|(keys, action)| (keys, action))
+                .chain(ours[i + 1..].iter().map(|(keys, action, _)| (keys, action)));
+            for (other, other_action) in others {
+                let (short, long) = match (keys.len(), other.len()) {
+                    (a, b) if a < b && other.starts_with(keys) => {
+                        ((keys, action), (other, other_action))
+                    }
+                    (a, b) if b < a && keys.starts_with(other) => {
+                        ((other, other_action), (keys, action))
+                    }
+                    _ => continue,
+                };
+                errors.push((
+                    *n,
+                    format!(
+                        "\"{}\" ({}) hides \"{}\" ({}), which starts with it",
+                        spaced(short.0),
+                        short.1.name(),
+                        spaced(long.0),
+                        long.1.name()
+                    ),
+                ));
+            }
+        }
+        if !errors.is_empty() {
+            errors.sort_by_key(|(n, _)| *n);
+            return Err(errors);
+        }
+        // first, so the footer shows them
+        self.bindings.splice(
+            0..0,
+            ours.into_iter().map(|(keys, action, _)| (keys, action)),
+        );
+        Ok(())
+    }
+
+    /// Every binding, such as `j/k : down/up`, with the first action of
+    /// each. An action bound to several keys shows the first.
+    fn entries(&self) -> Vec<(Action, String)> {
+        let first = |action: Action| {
+            self.bindings
+                .iter()
+                .find(|(_, bound)| *bound == action)
+                .map(|(keys, _)| show(keys))
+        };
+        let mut entries = vec![];
+        for &(actions, label) in FOOTER {
+            let keys: Vec<Option<String>> = actions.iter().map(|&action| first(action)).collect();
+            if keys.iter().all(Option::is_some) {
+                let keys: Vec<String> = keys.into_iter().flatten().collect();
+                entries.push((actions[0], format!("{} : {}", keys.join("/"), label)));
+            } else {
+                for (&action, keys) in actions.iter().zip(keys) {
+                    if let Some(keys) = keys {
+                        entries.push((action, format!("{} : {}", keys, action.label())));
+                    }
+                }
+            }
+        }
+        entries
+    }
+
+    /// Every binding, one per entry, for the help overlay.
+    pub fn help(&self) -> Vec<String> {
+        self.entries().into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// The footer, such as `j/k : down/up | q : quit | ? : all keys`: the
+    /// entries that fit in `width` columns, in order and skipping the ones
+    /// that don't, then the key that lists them all.
+    pub fn footer(&self, width: usize) -> String {
+        const SEP: &str = " | ";
+        let (help, entries): (Vec<_>, Vec<_>) = self
+            .entries()
+            .into_iter()
+            .partition(|&(action, _)| action == Action::Help);
+        // the help entry and the separator before it
+        let reserved = help
+            .first()
+            .map_or(0, |(_, entry)| SEP.len() + entry.chars().count());
+        let mut footer = String::new();
+        for (_, entry) in entries {
+            let sep = if footer.is_empty() { "" } else { SEP };
+            if footer.chars().count() + sep.len() + entry.chars().count() + reserved > width {
+                continue;
+            }
+            footer.push_str(sep);
+            footer.push_str(&entry);
+        }
+        for (_, entry) in help {
+            if !footer.is_empty() {
+                footer.push_str(SEP);
+            }
+            footer.push_str(&entry);
+        }
+        footer
+    }
+
+    /// Adds `key` to the keys in `pending` and says whether they now make
+    /// up a binding.
+    pub fn feed(&self, pending: &mut Pending, key: KeyEvent) -> Outcome {
+        let chord = Chord::from_event(key);
+        if chord.code == KeyCode::Esc && !pending.is_empty() {
+            *pending = Pending::default();
+            return Outcome::Pending;
+        }
+        // counts are part of the vim keymap only
+        if let (true, true, KeyCode::Char(c @ '0'..='9')) =
+            (self.vim, pending.keys.is_empty(), chord.code)
+        {
+            let bound = self.bindings.iter().any(|(keys, _)| keys[0] == chord);
+            let counting = pending.count.is_some() || (c != '0' && !bound);
+            if counting && chord.modifiers.is_empty() {
+                let digit = c as usize - '0' as usize;
+                let count = pending.count.unwrap_or(0).saturating_mul(10);
+                pending.count = Some(count.saturating_add(digit).min(MAX_COUNT));
+                return Outcome::Pending;
+            }
+        }
+
+        pending.keys.push(chord);
+        let exact = self
+            .bindings
+            .iter()
+            .find(|(keys, _)| *keys == pending.keys)
+            .map(|&(_, action)| action);
+        if exact.is_none() && self.starts_binding(&pending.keys) {
+            return Outcome::Pending;
+        }
+        let count = pending.count;
+        *pending = Pending::default();
+        match exact {
+            Some(action) => Outcome::Action(action, count.unwrap_or(1).max(1), count.is_some()),
+            None => Outcome::Unbound,
+        }
+    }
+
+    /// Whether some binding is longer than `keys` and starts with them.
+    fn starts_binding(&self, keys: &[Chord]) -> bool {
+        self.bindings
+            .iter()
+            .any(|(bound, _)| bound.len() > keys.len() && bound.starts_with(keys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(keymap: &Keymap, pending: &mut Pending, keys: &str) -> Option<(Action, usize)> {
+        let mut last = None;
+        for c in keys.chars() {
+            let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+            last = match keymap.feed(pending, key) {
+                Outcome::Action(action, count, _) => Some((action, count)),
+                _ => None,
+            };
+        }
+        last
+    }
+
+    fn chord(word: &str) -> Chord {
+        Chord::parse(word).unwrap_or_else(|| panic!("failed to parse {:?}", word))
+    }
+
+    fn errors(keymap: &mut Keymap, text: &str) -> Vec<(usize, String)> {
+        keymap.configure(text).unwrap_err()
+    }
+
+    #[test]
+    fn chords_read_back_what_they_show() {
+        for word in [
+            "x",
+            "G",
+            "/",
+            "Ctrl-D",
+            "Alt-x",
+            "Ctrl-Alt-K",
+            "Shift-Tab",
+            "Space",
+            "Enter",
+            "PageDown",
+            "F5",
+            "Down",
+        ] {
+            let chord = chord(word);
+            assert_eq!(chord.to_string(), word);
+            assert_eq!(Chord::parse(&chord.to_string()), Some(chord));
+        }
+        assert_eq!(chord("ctrl-d"), Chord::ctrl('d'));
+        assert_eq!(chord("Shift-g"), chord("G"));
+        assert_eq!(chord("space"), Chord::key(KeyCode::Char(' ')));
+        assert_eq!(chord("-"), Chord::key(KeyCode::Char('-')));
+        for word in ["", "Shift-1", "F13", "Hyper-x", "Ctrl-", "gg"] {
+            assert_eq!(Chord::parse(word), None, "{:?}", word);
+        }
+    }
+
+    #[test]
+    fn configure_rebinds_and_unbinds() {
+        let mut keymap = Keymap::vim();
+        keymap
+            .configure("# comment\n\nCtrl-n down\nx   delete\nd d none\n")
+            .unwrap();
+        let mut pending = Pending::default();
+        let ctrl_n = KeyEvent::new(KeyCode::Char('n'), KeyModifiers::CONTROL);
+        assert!(matches!(
+            keymap.feed(&mut pending, ctrl_n),
+            Outcome::Action(Action::Down, 1, false)
+        ));
+        assert_eq!(press(&keymap, &mut pending, "x"), Some((Action::Delete, 1)));
+        assert_eq!(press(&keymap, &mut pending, "dd"), None);
+        // j still works, it was only given another key
+        assert_eq!(press(&keymap, &mut pending, "j"), Some((Action::Down, 1)));
+    }
+
+    #[test]
+    fn configure_errors_come_with_their_line() {
+        let mut keymap = Keymap::default_keys();
+        assert_eq!(
+            errors(
+                &mut keymap,
+                "x delete\nx\ny launch\nHyper-q quit\nx archive\nz z sort\n"
+            ),
+            vec![
+                (
+                    2,
+                    "Expected keys and an action, like \"Ctrl-n down\"".to_string()
+                ),
+                (3, "Unknown action \"launch\"".to_string()),
+                (
+                    4,
+                    "Unknown key \"Hyper-q\" (separate keys with spaces, as in \"g g\")"
+                        .to_string()
+                ),
+                (5, "\"x\" is already bound on line 1".to_string()),
+                (
+                    6,
+                    "\"z\" (archive) hides \"z z\" (sort), which starts with it".to_string()
+                ),
+            ]
+        );
+        // nothing is applied when there are errors
+        let mut pending = Pending::default();
+        assert_eq!(press(&keymap, &mut pending, "x"), None);
+    }
+
+    #[test]
+    fn prefix_conflicts_within_the_file() {
+        let mut keymap = Keymap::default_keys();
+        assert_eq!(
+            errors(&mut keymap, "g x due\n# between\ng agenda\n"),
+            vec![(
+                1,
+                "\"g\" (agenda) hides \"g x\" (due), which starts with it".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn footer_follows_the_bindings() {
+        let mut keymap = Keymap::vim();
+        let help = keymap.help();
+        assert_eq!(help[..2], ["j/k : down/up", "gg/G : top/bottom"]);
+        assert!(help.contains(&"yy/p : yank/paste note".to_string()));
+        assert_eq!(help.last().unwrap(), "? : all keys");
+        keymap.configure("y y none\nCtrl-j down").unwrap();
+        let help = keymap.help();
+        assert_eq!(help[0], "Ctrl-J/k : down/up");
+        // half of a pair unbound: the other half is listed on its own
+        assert!(help.contains(&"p : paste note".to_string()));
+        assert!(!help.iter().any(|entry| entry.contains("yank")));
+    }
+
+    #[test]
+    fn footer_fits_the_width() {
+        for keymap in [Keymap::default_keys(), Keymap::vim()] {
+            for width in [30, 60, 80, 120, 200] {
+                let footer = keymap.footer(width);
+                assert!(footer.chars().count() <= width, "{}: {:?}", width, footer);
+                assert!(footer.ends_with("? : all keys"), "{:?}", footer);
+            }
+            assert_eq!(keymap.footer(80), keymap.footer(80).trim());
+            // wide enough for everything, it is every entry
+            assert_eq!(keymap.footer(usize::MAX), keymap.help().join(" | "));
+        }
+        assert_eq!(
+            Keymap::default_keys().footer(40),
+            "j/k : down/up | q : quit | ? : all keys"
+        );
+    }
+
+    #[test]
+    fn counts_need_the_vim_keymap() {
+        let keymap = Keymap::default_keys();
+        let mut pending = Pending::default();
+        assert_eq!(press(&keymap, &mut pending, "5"), None);
+        assert!(pending.is_empty());
+        assert_eq!(press(&keymap, &mut pending, "d"), Some((Action::Delete, 1)));
+        assert_eq!(
+            press(&keymap, &mut pending, "1"),
+            Some((Action::ShowNotes, 1))
+        );
+    }
+
+    #[test]
+    fn counts_stop_at_the_maximum() {
+        let keymap = Keymap::vim();
+        let mut pending = Pending::default();
+        assert_eq!(press(&keymap, &mut pending, "5j"), Some((Action::Down, 5)));
+        let digits = "9".repeat(40);
+        assert_eq!(
+            press(&keymap, &mut pending, &format!("{}j", digits)),
+            Some((Action::Down, MAX_COUNT))
+        );
+    }
+}
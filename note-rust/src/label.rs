@@ -0,0 +1,55 @@
+//! Color labels for grouping notes at a glance.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Label {
+    Red,
+    Orange,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+    Gray,
+}
+
+impl Label {
+    /// The palette, in the order the label popup lists it.
+    pub const ALL: [Label; 7] = [
+        Label::Red,
+        Label::Orange,
+        Label::Yellow,
+        Label::Green,
+        Label::Blue,
+        Label::Purple,
+        Label::Gray,
+    ];
+
+    pub fn parse(s: &str) -> Option<Label> {
+        match s.trim().to_lowercase().as_str() {
+            "red" => Some(Label::Red),
+            "orange" => Some(Label::Orange),
+            "yellow" => Some(Label::Yellow),
+            "green" => Some(Label::Green),
+            "blue" => Some(Label::Blue),
+            "purple" => Some(Label::Purple),
+            "gray" | "grey" => Some(Label::Gray),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Label::Red => "red",
+            Label::Orange => "orange",
+            Label::Yellow => "yellow",
+            Label::Green => "green",
+            Label::Blue => "blue",
+            Label::Purple => "purple",
+            Label::Gray => "gray",
+        };
+        write!(f, "{}", name)
+    }
+}
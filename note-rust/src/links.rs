@@ -0,0 +1,212 @@
+//! `[[Note Title]]` links between notes.
+//!
+//! A link target is matched against note titles, ignoring case and
+//! surrounding spaces. If no title matches and the target is a number, it is
+//! the note number shown in the TUI list (`[[3]]`). Deleting a note renumbers
+//! the notes after it, so numeric links are rewritten along with it: links
+//! to the deleted note name its title, unless the body would get too long.
+
+use std::cmp::Ordering;
+
+use crate::{NoteFormat, MAX_LINE};
+
+/// Targets of the `[[...]]` links in `body`, in order.
+pub fn targets(body: &str) -> Vec<&str> {
+    let mut targets = vec![];
+    let mut rest = body;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
+        };
+        let target = after[..end].trim();
+        if !target.is_empty() {
+            targets.push(target);
+        }
+        rest = &after[end + 2..];
+    }
+    targets
+}
+
+/// Whether link target `target` names the note titled `title`.
+pub fn same_title(target: &str, title: &str) -> bool {
+    target.trim().to_lowercase() == title.trim().to_lowercase()
+}
+
+/// The note number `target` stands for, if it is a number and no title
+/// matches it.
+fn number(target: &str, notes: &[NoteFormat]) -> Option<usize> {
+    if notes.iter().any(|note| same_title(target, &note.text)) {
+        return None;
+    }
+    target.trim().parse().ok()
+}
+
+/// Index of the note `target` points to.
+pub fn resolve(target: &str, notes: &[NoteFormat]) -> Option<usize> {
+    notes
+        .iter()
+        .position(|note| same_title(target, &note.text))
+        .or_else(|| {
+            let n = number(target, notes)?;
+            (1..=notes.len()).contains(&n).then(|| n - 1)
+        })
+}
+
+/// Notes whose body links to note `index`.
+pub fn backlinks(notes: &[NoteFormat], index: usize) -> Vec<usize> {
+    (0..notes.len())
+        .filter(|&i| i != index)
+        .filter(|&i| {
+            targets(&notes[i].body)
+                .iter()
+                .any(|target| resolve(target, notes) == Some(index))
+        })
+        .collect()
+}
+
+/// `body` with every link to `old_title` pointed at `new_title` instead, or
+/// `None` if it has no such link.
+pub fn retarget(body: &str, old_title: &str, new_title: &str) -> Option<String> {
+    map_links(body, |target| {
+        same_title(target, old_title).then(|| format!("[[{}]]", new_title))
+    })
+}
+
+/// New bodies for the notes whose numeric links change when note `index` is
+/// removed: links to it name its title instead and links to later notes
+/// count one less. Where the title would make a body longer than a line,
+/// the links to the removed note are dropped; those notes are returned
+/// second. Indices are the ones after the removal.
+pub fn after_removal(notes: &[NoteFormat], index: usize) -> (Vec<(usize, String)>, Vec<usize>) {
+    let rewrite = |body: &str, title: Option<&str>| {
+        map_links(body, |target| {
+            let n = number(target, notes)?;
+            match n.checked_sub(1)?.cmp(&index) {
+                Ordering::Less => None,
+                Ordering::Equal => {
+                    Some(title.map_or(String::new(), |title| format!("[[{}]]", title)))
+                }
+                Ordering::Greater => (n <= notes.len()).then(|| format!("[[{}]]", n - 1)),
+            }
+        })
+    };
+    let title = notes[index].text.trim();
+    let mut bodies = vec![];
+    let mut dropped = vec![];
+    for i in (0..notes.len()).filter(|&i| i != index) {
+        let Some(mut body) = rewrite(&notes[i].body, Some(title)) else {
+            continue;
+        };
+        let at = if i > index { i - 1 } else { i };
+        if body.chars().count() > MAX_LINE {
+            // renumbering alone never makes a body longer
+            body = rewrite(&notes[i].body, None).unwrap_or(body);
+            dropped.push(at);
+        }
+        bodies.push((at, body));
+    }
+    (bodies, dropped)
+}
+
+/// `body` with each link replaced by what `f` returns for its target, or
+/// `None` if `f` replaced none.
+fn map_links(body: &str, mut f: impl FnMut(&str) -> Option<String>) -> Option<String> {
+    let mut out = String::with_capacity(body.len());
+    let mut changed = false;
+    let mut rest = body;
+    while let Some(start) = rest.find("[[") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("]]") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        if let Some(link) = f(after[..end].trim()) {
+            out.push_str(&link);
+            changed = true;
+        } else {
+            out.push_str(&rest[start..start + 2 + end + 2]);
+        }
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    changed.then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes(notes: &[(&str, &str)]) -> Vec<NoteFormat> {
+        notes
+            .iter()
+            .map(|(text, body)| NoteFormat {
+                text: text.to_string(),
+                body: body.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn titles_match_ignoring_case() {
+        let notes = notes(&[("Über Ideas", ""), ("3", ""), ("Plan", "")]);
+        assert_eq!(resolve("über ideas", &notes), Some(0));
+        assert_eq!(resolve(" PLAN ", &notes), Some(2));
+        // a title wins over a note number
+        assert_eq!(resolve("3", &notes), Some(1));
+        assert_eq!(resolve("1", &notes), Some(0));
+        assert_eq!(resolve("4", &notes), None);
+        assert_eq!(
+            retarget("see [[über ideas]]", "Über Ideas", "Ideas"),
+            Some("see [[Ideas]]".to_string())
+        );
+    }
+
+    #[test]
+    fn removing_a_note_renumbers_links() {
+        let notes = notes(&[
+            ("a", "[[2]] [[3]] [[4]] [[c]]"),
+            ("b", "[[1]]"),
+            ("c", "[[4]] [[9]]"),
+            ("d", "no links"),
+        ]);
+        assert_eq!(
+            after_removal(&notes, 1),
+            (
+                vec![
+                    (0, "[[b]] [[2]] [[3]] [[c]]".to_string()),
+                    (1, "[[3]] [[9]]".to_string()),
+                ],
+                vec![]
+            )
+        );
+        assert_eq!(
+            after_removal(&notes, 3),
+            (
+                vec![
+                    (0, "[[2]] [[3]] [[d]] [[c]]".to_string()),
+                    (2, "[[d]] [[9]]".to_string())
+                ],
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    fn links_are_dropped_when_the_title_does_not_fit() {
+        let long = "t".repeat(60);
+        let body = format!("{} [[2]] [[3]]", "x".repeat(80));
+        let notes = notes(&[("a", &body), (&long, ""), ("c", "see [[2]]")]);
+        assert_eq!(
+            after_removal(&notes, 1),
+            (
+                vec![
+                    (0, format!("{}  [[2]]", "x".repeat(80))),
+                    (1, format!("see [[{}]]", long)),
+                ],
+                vec![0]
+            )
+        );
+    }
+}
@@ -1,31 +1,83 @@
+#![allow(clippy::too_many_arguments)]
+
+mod agenda;
+mod attach;
+mod cli;
+mod clipboard;
+mod dateparse;
+mod fields;
+mod filter;
+mod fuzzy;
+mod input;
+mod journal;
+mod keymap;
+mod label;
+mod links;
+mod meta;
+mod priority;
+mod recur;
+mod review;
+mod search;
+mod template;
+mod theme;
+mod timelog;
+mod toast;
+mod tree;
+mod undo;
+mod view;
+
+use agenda::{Bucket, Due};
+use chrono::{Local, NaiveDateTime};
 use color_eyre::eyre::Result;
 use crossterm::{
     // cursor::{MoveDown, MoveTo, Show},
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    event::{self, Event, KeyCode, KeyEvent},
     execute,
     //style::Print,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use fields::Fields;
+use filter::Filter;
+use input::TextInput;
+use keymap::{Action, Keymap, Outcome, Pending};
+use label::Label;
+use priority::Priority;
 use ratatui::{
     backend::CrosstermBackend,
-    layout::Alignment,
     prelude::*,
     text::Text,
-    widgets::{Block, BorderType, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
     Terminal,
 };
+use recur::Recurrence;
+use review::Card;
+use search::Search;
+use std::collections::HashSet;
+use template::Template;
+use theme::Theme;
+use timelog::Session;
+use toast::{Level, Toast, Toasts};
+use tree::FolderRow;
+use undo::{Change, UndoHistory};
+use view::{Panel, SortMode, ViewOptions};
 
 use std::fs::OpenOptions;
-use std::io::{Seek, SeekFrom, Write};
+use std::io::Write;
 use std::{
     fs::File,
     io::{self, BufRead, BufReader},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-static file_path: &str = "../note.txt";
+static DEFAULT_FILE_PATH: &str = "../note.txt";
+
+/// Path of the notebook. `NOTE_FILE` overrides it so the zsh script can point
+/// the CLI at its own `note.txt`.
+fn file_path() -> String {
+    std::env::var("NOTE_FILE").unwrap_or_else(|_| DEFAULT_FILE_PATH.to_string())
+}
 
-fn note_title_input(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+fn note_title_input(percent_x: u16, _percent_y: u16, r: Rect) -> Rect {
     let popup_width = r.width * percent_x / 150;
     let popup_height = 3;
     let popup_x = r.x + (r.width - popup_width) / 2;
@@ -33,7 +85,7 @@ fn note_title_input(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     Rect::new(popup_x, popup_y, popup_width, popup_height)
 }
 
-fn note_body_input(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+fn note_body_input(percent_x: u16, _percent_y: u16, r: Rect) -> Rect {
     let popup_width = r.width * percent_x / 150;
     let popup_height = 9;
     let popup_x = r.x + (r.width - popup_width) / 2;
@@ -41,175 +93,590 @@ fn note_body_input(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     Rect::new(popup_x, popup_y, popup_width, popup_height)
 }
 
+fn agenda_popup(r: Rect) -> Rect {
+    let popup_width = r.width * 4 / 5;
+    let popup_height = r.height * 4 / 5;
+    let popup_x = r.x + (r.width - popup_width) / 2;
+    let popup_y = r.y + (r.height - popup_height) / 2;
+    Rect::new(popup_x, popup_y, popup_width, popup_height)
+}
+
 fn load_notes() -> Result<Vec<String>> {
-    let file = File::open(file_path).unwrap_or_else(|_| File::create(file_path).unwrap());
+    let path = file_path();
+    let file = File::open(&path).unwrap_or_else(|_| File::create(&path).unwrap());
     let reader = BufReader::new(file);
-    Ok(reader.lines().filter_map(Result::ok).collect())
+    Ok(reader.lines().map_while(Result::ok).collect())
+}
+
+fn load_note_list() -> Result<Vec<NoteFormat>> {
+    let notes_raw: Vec<String> = load_notes()?
+        .into_iter()
+        .map(|line| line.trim_end().to_string())
+        .collect();
+    let meta_lines = meta::load_lines(&meta::meta_path(&file_path()))?;
+
+    let mut notes: Vec<NoteFormat> = vec![];
+    let mut i = 0;
+    while i + 1 < notes_raw.len() {
+        let mut note = NoteFormat {
+            text: notes_raw[i].clone(),
+            body: notes_raw[i + 1].clone(),
+            ..Default::default()
+        };
+        if let Some(line) = meta_lines.get(i / 2) {
+            note.apply_meta(line);
+        }
+        notes.push(note);
+        i += 2;
+    }
+    Ok(notes)
+}
+
+/// Rewrites `note.txt` and `note.meta` from `notes`.
+fn save_notes(notes: &[NoteFormat]) -> io::Result<()> {
+    let mut file = File::create(file_path())?;
+    for note in notes {
+        writeln!(file, "{:<100}", note.text)?;
+        writeln!(file, "{:<100}", note.body)?;
+    }
+    save_meta(notes)
+}
+
+fn save_meta(notes: &[NoteFormat]) -> io::Result<()> {
+    let lines: Vec<String> = notes.iter().map(NoteFormat::meta_line).collect();
+    meta::save_lines(&meta::meta_path(&file_path()), &lines)
+}
+
+/// Days after which done notes are archived automatically.
+/// `NOTE_AUTO_ARCHIVE_DAYS=0` turns auto-archiving off.
+fn auto_archive_days() -> i64 {
+    std::env::var("NOTE_AUTO_ARCHIVE_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(30)
+}
+
+/// Archives notes that were marked done more than `days` days ago and
+/// returns how many were archived.
+fn auto_archive(notes: &mut [NoteFormat], now: NaiveDateTime, days: i64) -> usize {
+    if days <= 0 {
+        return 0;
+    }
+    let cutoff = now - chrono::Duration::days(days);
+    let mut count = 0;
+    for note in notes.iter_mut() {
+        if !note.archived && note.done.is_some_and(|done| done < cutoff) {
+            note.archived = true;
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Folder tree rows for the notes in `panel`.
+fn folder_rows(notes: &[NoteFormat], panel: Panel, collapsed: &HashSet<String>) -> Vec<FolderRow> {
+    let archived = panel == Panel::Archive;
+    let folders: Vec<&str> = notes
+        .iter()
+        .filter(|note| note.archived == archived)
+        .map(|note| note.folder.as_str())
+        .collect();
+    tree::rows(&folders, collapsed)
+}
+
+/// Switches the panel and folder so that note `index` is in the list.
+fn show_note(
+    notes: &[NoteFormat],
+    index: usize,
+    options: &mut ViewOptions,
+    tree_state: &mut ListState,
+) {
+    options.panel = if notes[index].archived {
+        Panel::Archive
+    } else {
+        Panel::Notes
+    };
+    let in_folder = options
+        .folder
+        .as_ref()
+        .is_none_or(|folder| tree::contains(folder, &notes[index].folder));
+    if !in_folder {
+        tree_state.select(Some(0));
+    }
+}
+
+/// `text` as spans with the matches of `query` highlighted.
+fn highlighted(text: &str, query: Option<&str>) -> Vec<Span<'static>> {
+    let mut spans = vec![];
+    let mut last = 0;
+    for (start, end) in query.map(|q| search::find_all(text, q)).unwrap_or_default() {
+        spans.push(Span::raw(text[last..start].to_string()));
+        spans.push(Span::styled(
+            text[start..end].to_string(),
+            theme::current().search_match,
+        ));
+        last = end;
+    }
+    spans.push(Span::raw(text[last..].to_string()));
+    spans
+}
+
+/// `columns` are the custom fields shown before the title, with the width
+/// of their longest value.
+fn note_list_item(
+    index: usize,
+    note: &NoteFormat,
+    columns: &[(&str, usize)],
+    search: &Search,
+) -> ListItem<'static> {
+    let theme = theme::current();
+    let marker = match note.priority {
+        Priority::None => Span::raw("  "),
+        priority => Span::styled("● ", theme.priority(priority).add_modifier(Modifier::BOLD)),
+    };
+    let pin = if note.pinned { "📌 " } else { "" };
+    let check = if note.done.is_some() { "[x] " } else { "" };
+    let mut label = String::new();
+    let mut style = Style::default();
+    if let Some(due) = note.due {
+        label.push_str(&format!("  [due {}]", due));
+        if note.done.is_none() {
+            match due.bucket(Local::now().naive_local()) {
+                Bucket::Overdue => style = style.patch(theme.overdue),
+                Bucket::Today => style = style.patch(theme.due_today),
+                _ => {}
+            }
+        }
+    }
+    if let Some(recur) = &note.recur {
+        label.push_str(&format!("  [↻ {}, done {}x]", recur, note.history.len()));
+    }
+    let spent = timelog::total(note, None, Local::now().naive_local());
+    if note.timer.is_some() {
+        label.push_str(&format!(
+            "  [⏱ {} running]",
+            timelog::format_duration(spent)
+        ));
+    } else if !note.sessions.is_empty() {
+        label.push_str(&format!("  [⏱ {}]", timelog::format_duration(spent)));
+    }
+    if note.done.is_some() {
+        style = style.patch(theme.done);
+    }
+    let mut spans = vec![marker];
+    if let Some(label) = note.label {
+        spans.push(Span::styled("■ ", theme.label(label)));
+    }
+    for (key, width) in columns {
+        let value = note.fields.get(key).unwrap_or("");
+        spans.push(Span::styled(
+            format!("{:<width$}  ", value, width = width),
+            theme.accent,
+        ));
+    }
+    let (title_query, body_query) = search.highlights();
+    spans.push(Span::raw(format!("{}: {}{}\"", index + 1, pin, check)));
+    spans.extend(highlighted(&note.text, title_query));
+    spans.push(Span::raw("\" - \""));
+    spans.extend(highlighted(&note.body, body_query));
+    spans.push(Span::raw("\""));
+    spans.push(Span::styled(label, theme.tag));
+    ListItem::new(Line::from(spans)).style(style)
 }
 fn append_note_to_file(note: &str, body: &str) -> Result<()> {
     let mut file = OpenOptions::new()
         .append(true)
         .create(true)
-        .open(file_path)?;
+        .open(file_path())?;
     writeln!(file, "{:<100}", note)?;
     writeln!(file, "{:<100}", body)?;
     Ok(())
 }
 
-fn draw_main_ui(f: &mut Frame, items: &Vec<ListItem>, list_state: &mut ListState) {
+/// Appends `note` to the notebook, stamped with its creation time. The
+/// metadata is written first, so if appending to `note.txt` fails the note
+/// is left out of `notes` and trying again doesn't add it twice.
+fn add_note(notes: &mut Vec<NoteFormat>, mut note: NoteFormat) -> Result<()> {
+    note.created = Some(Local::now().naive_local());
+    let (text, body) = (note.text.clone(), note.body.clone());
+    notes.push(note);
+    let saved = save_meta(notes)
+        .map_err(Into::into)
+        .and_then(|()| append_note_to_file(&text, &body));
+    if let Err(err) = saved {
+        notes.pop();
+        // NOTE: best effort, a metadata line without a note is ignored
+        let _ = save_meta(notes);
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn folder_tree_item(row: &FolderRow, collapsed: &HashSet<String>) -> ListItem<'static> {
+    let arrow = if !row.has_children {
+        "  "
+    } else if collapsed.contains(&row.path) {
+        "▸ "
+    } else {
+        "▾ "
+    };
+    ListItem::new(format!(
+        "{}{}{} ({})",
+        "  ".repeat(row.depth.saturating_sub(1)),
+        arrow,
+        row.name,
+        row.count
+    ))
+}
+
+fn draw_main_ui(
+    f: &mut Frame,
+    items: &[ListItem],
+    list_state: &mut ListState,
+    tree_items: &[ListItem],
+    tree_state: &mut ListState,
+    tree_focus: bool,
+    options: &ViewOptions,
+    backlinks: &[ListItem],
+    attachments: &[ListItem],
+    status: &str,
+    keymap: &Keymap,
+    pending: &str,
+) {
     let size = f.area();
 
-    let list_block_area = Rect::new(0, 0, size.width, size.height - 3);
+    let main_area = Rect::new(0, 0, size.width, size.height - 3);
     let cmd_block_area = Rect::new(0, size.height - 3, size.width, 3);
+    let [tree_block_area, notes_area] =
+        Layout::horizontal([Constraint::Length(size.width / 4), Constraint::Min(0)])
+            .areas(main_area);
+    let [list_block_area, details_area] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(6)]).areas(notes_area);
+    let [backlinks_area, attachments_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .areas(details_area);
+
+    let theme = theme::current();
+    let tree = List::new(tree_items.to_vec())
+        .block(
+            Block::default()
+                .title("Folders")
+                .border_type(BorderType::Rounded)
+                .borders(Borders::ALL)
+                .border_style(if tree_focus {
+                    theme.focused
+                } else {
+                    theme.border
+                }),
+        )
+        .highlight_style(theme::current().selected);
+    f.render_stateful_widget(tree, tree_block_area, tree_state);
+
+    let mut title = options.panel.title().to_string();
+    if let Some(folder) = &options.folder {
+        title.push_str(&format!(" in {}", folder));
+    }
+    if let Some(filter) = &options.field {
+        title.push_str(&format!(" where {}", filter));
+    }
+    if let Some(filter) = &options.filter {
+        title.push_str(&format!(" [filter: {}]", filter));
+    }
+    if options.sort != SortMode::Manual {
+        title.push_str(&format!(" (sorted by {})", options.sort.label()));
+    }
+    let list = List::new(items.to_vec())
+        .block(
+            Block::default()
+                .title(title)
+                .border_type(BorderType::Rounded)
+                .borders(Borders::ALL)
+                .border_style(if tree_focus {
+                    theme.border
+                } else {
+                    theme.focused
+                }),
+        )
+        .highlight_symbol(">> ")
+        .highlight_style(theme::current().selected);
+
+    f.render_stateful_widget(list, list_block_area, list_state);
 
-    let list = List::new(items.clone()).block(
+    let backlinks_list = List::new(backlinks.to_vec()).block(
         Block::default()
-            .title("[1]: Notes")
+            .title(format!("Backlinks ({})", backlinks.len()))
             .border_type(BorderType::Rounded)
-            .borders(Borders::ALL),
+            .borders(Borders::ALL)
+            .border_style(theme.border),
     );
-    let list = List::new(items.clone())
-        .highlight_symbol(">> ")
-        .highlight_style(Style::default().bg(Color::Blue));
+    f.render_widget(backlinks_list, backlinks_area);
 
-    f.render_stateful_widget(list, list_block_area, list_state);
+    let attachments_list = List::new(attachments.to_vec()).block(
+        Block::default()
+            .title(format!("Attachments ({})", attachments.len()))
+            .border_type(BorderType::Rounded)
+            .borders(Borders::ALL)
+            .border_style(theme.border),
+    );
+    f.render_widget(attachments_list, attachments_area);
 
+    // keys typed towards a longer binding, like vim's showcmd
     let cmd_block = Block::default()
-        .title("")
+        .title(status.to_string())
+        .title(Line::from(pending.to_string()).right_aligned())
         .border_type(BorderType::Rounded)
-        .borders(Borders::ALL);
+        .borders(Borders::ALL)
+        .border_style(theme.border);
 
-    let cmd_paragraph = Paragraph::new(Text::from(
-        "j : page down | k : page up | q : quit | a : add note | e : edit command | Enter : edit selected note",
-    ))
-    .block(cmd_block.clone());
+    let footer = keymap.footer(cmd_block_area.width.saturating_sub(2) as usize);
+    let cmd_paragraph = Paragraph::new(Text::from(footer)).block(cmd_block.clone());
     f.render_widget(cmd_block, cmd_block_area);
     f.render_widget(cmd_paragraph, cmd_block_area);
 }
 
+/// Titles and bodies are one line each in `note.txt`, padded to this width.
+const MAX_LINE: usize = 100;
+
+/// Why `text` can't be saved as the note's `part` ("Title" or "Body"), with
+/// the same wording as the zsh script. Only the title is required.
+fn line_error(part: &str, text: &str) -> Option<String> {
+    if part == "Title" && text.trim().is_empty() {
+        Some("Title is empty".to_string())
+    } else if text.chars().count() > MAX_LINE {
+        Some(format!("{} must be {} characters or less", part, MAX_LINE))
+    } else {
+        None
+    }
+}
+
+/// Character count for the bottom border of a title or body popup, in
+/// red once the text is too long.
+fn length_hint(text: &str) -> Line<'static> {
+    let count = text.chars().count();
+    let hint = Line::from(format!(" {}/{} ", count, MAX_LINE)).right_aligned();
+    if count > MAX_LINE {
+        hint.style(theme::current().error)
+    } else {
+        hint
+    }
+}
+
+/// A block for a popup, in the theme's popup colors.
+fn popup_block<'a>() -> Block<'a> {
+    Block::default()
+        .style(theme::current().popup)
+        .border_style(theme::current().popup_border)
+}
+
+/// The toast over the bottom right corner, above the footer.
+fn draw_toast(f: &mut Frame, toast: &Toast) {
+    let size = f.area();
+    let width = (toast.text.chars().count() as u16 + 4).min(size.width);
+    let area = Rect::new(
+        size.width - width,
+        size.height.saturating_sub(6),
+        width,
+        3.min(size.height),
+    );
+    let style = match toast.level {
+        Level::Info => theme::current().info,
+        Level::Error => theme::current().error,
+    };
+    let paragraph = Paragraph::new(toast.text.as_str()).block(
+        popup_block()
+            .border_type(BorderType::Rounded)
+            .borders(Borders::ALL)
+            .border_style(style),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+}
+
 fn draw_add_popup_title(
     f: &mut Frame,
-    area: Rect,
-    note: &mut NoteFormat,
+    title: &mut TextInput,
     key_event: KeyEvent,
     add_popup_active: &mut i8,
     action: &mut bool,
+    toasts: &mut Toasts,
 ) {
+    match key_event.code {
+        _ if title.handle(key_event) => {}
+        KeyCode::Enter => match line_error("Title", title.value()) {
+            Some(err) => toasts.error(err),
+            None => *add_popup_active = 2,
+        },
+        KeyCode::Esc => {
+            *action = false;
+            *add_popup_active = 0;
+            title.clear();
+        }
+        _ => {}
+    }
+    if *add_popup_active != 1 {
+        return;
+    }
     let area = note_title_input(60, 20, f.area());
-    let block = Block::default()
+    let block = popup_block()
         .title("New Note Title")
+        .title_bottom(length_hint(title.value()))
         .borders(Borders::ALL);
-    let paragraph = Paragraph::new(note.text.as_str()).block(block);
-    f.render_widget(paragraph, area);
+    title.render(f, area, block);
+}
 
+fn draw_add_popup_body(
+    f: &mut Frame,
+    title: &mut TextInput,
+    body: &mut TextInput,
+    notes: &mut Vec<NoteFormat>,
+    key_event: KeyEvent,
+    add_popup_active: &mut i8,
+    action: &mut bool,
+    line_cnt: &mut u32,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
+) -> Result<()> {
     match key_event.code {
+        _ if body.handle(key_event) => {}
         KeyCode::Enter => {
-            if !note.text.trim().is_empty() && note.text.len() <= 101 {
-                //TODO: add error message
-                *add_popup_active = 2;
+            if let Some(err) = line_error("Body", body.value()) {
+                toasts.error(err);
             } else {
-                *add_popup_active = 0;
+                let note = NoteFormat {
+                    text: title.value().to_string(),
+                    body: body.value().to_string(),
+                    ..Default::default()
+                };
+                // NOTE: on failure the popup stays open, so nothing typed is lost
+                add_note(notes, note)?;
+                *line_cnt = notes.len() as u32;
+                undo_history.record(Change::Added {
+                    index: notes.len() - 1,
+                    note: notes[notes.len() - 1].clone(),
+                });
+                toasts.info(format!("Added note {}", notes.len()));
+                title.clear();
+                body.clear();
                 *action = false;
+                *add_popup_active = 0;
+                return Ok(());
             }
         }
         KeyCode::Esc => {
-            *action = false;
             *add_popup_active = 0;
-            note.text.clear();
-        }
-        KeyCode::Backspace => {
-            note.text.pop();
-        }
-        KeyCode::Char(c) => {
-            note.text.push(c);
+            *action = false;
+            title.clear();
+            body.clear();
+            return Ok(());
         }
         _ => {}
     }
+    let block = popup_block()
+        .title("New Note Body")
+        .title_bottom(length_hint(body.value()))
+        .borders(Borders::ALL);
+    let area = note_body_input(60, 20, f.area());
+    body.render(f, area, block);
+    Ok(())
 }
-
-fn draw_add_popup_body(
+/// First step of `a` when there are templates: pick one, or a blank note.
+fn draw_add_popup_template(
     f: &mut Frame,
-    area: Rect,
-    note: &mut NoteFormat,
-    notes: &mut Vec<NoteFormat>,
+    title: &mut TextInput,
+    body: &mut TextInput,
+    templates: &[Template],
+    template_state: &mut ListState,
     key_event: KeyEvent,
-    items: &mut Vec<ListItem>,
     add_popup_active: &mut i8,
     action: &mut bool,
-    line_cnt: &mut u32,
-) -> Result<()> {
-    let block = Block::default()
-        .title("New Note Body")
-        .borders(Borders::ALL);
-    let paragraph = Paragraph::new(note.body.as_str())
-        .block(block)
-        .wrap(Wrap { trim: true })
-        .alignment(Alignment::Left);
+) {
+    let mut items = vec![ListItem::new("Blank note")];
+    items.extend(
+        templates
+            .iter()
+            .map(|template| ListItem::new(template.name.clone())),
+    );
     let area = note_body_input(60, 20, f.area());
-    f.render_widget(paragraph, area);
+    let list = List::new(items)
+        .block(popup_block().title("New Note from").borders(Borders::ALL))
+        .highlight_symbol(">> ")
+        .highlight_style(theme::current().selected);
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, template_state);
 
+    let count = templates.len() + 1;
     match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            let i = template_state.selected().unwrap_or(0);
+            template_state.select(Some((i + 1) % count));
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            let i = template_state.selected().unwrap_or(0);
+            template_state.select(Some(if i == 0 { count - 1 } else { i - 1 }));
+        }
         KeyCode::Enter => {
-            if note.body.len() <= 101 {
-                //TODO: add error message
-                *line_cnt = (notes.len() + 1) as u32;
-                append_note_to_file(&note.text, &note.body)?;
-                items.push(ListItem::new(format!(
-                    "{}: \"{}\" - \"{}\"",
-                    line_cnt, note.text, note.body
-                )));
-                notes.push(note.clone());
-                *note = NoteFormat::default();
-                *action = false;
-                *add_popup_active = 0;
+            let selected = template_state.selected().unwrap_or(0);
+            if let Some(template) = selected.checked_sub(1).and_then(|i| templates.get(i)) {
+                let (text, expanded) = template.expand(Local::now().naive_local());
+                *title = TextInput::new(text);
+                *body = TextInput::new(expanded);
             }
+            *add_popup_active = 1;
         }
         KeyCode::Esc => {
             *add_popup_active = 0;
             *action = false;
-            note.text.clear();
-            note.body.clear();
-        }
-        KeyCode::Backspace => {
-            note.body.pop();
-        }
-        KeyCode::Char(c) => {
-            note.body.push(c);
         }
         _ => {}
     }
-    Ok(())
 }
+
 fn add_command(
     //TODO: fix cmd_help
     //HACK: More fast
     f: &mut Frame,
     add_popup_active: &mut i8,
     notes: &mut Vec<NoteFormat>,
-    items: &mut Vec<ListItem>,
-    note: &mut NoteFormat,
+    title: &mut TextInput,
+    body: &mut TextInput,
     key_event: KeyEvent,
     action: &mut bool,
     line_cnt: &mut u32,
+    templates: &[Template],
+    template_state: &mut ListState,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
 ) -> Result<()> {
     *action = true;
-    let area = note_body_input(60, 20, f.area());
 
     match *add_popup_active {
+        3 => {
+            draw_add_popup_template(
+                f,
+                title,
+                body,
+                templates,
+                template_state,
+                key_event,
+                add_popup_active,
+                action,
+            );
+        }
         1 => {
-            draw_add_popup_title(f, area, note, key_event, add_popup_active, action);
+            draw_add_popup_title(f, title, key_event, add_popup_active, action, toasts);
         }
         2 => {
             draw_add_popup_body(
                 f,
-                area,
-                note,
+                title,
+                body,
                 notes,
                 key_event,
-                items,
                 add_popup_active,
                 action,
                 line_cnt,
+                undo_history,
+                toasts,
             )?;
         }
         _ => {}
@@ -217,211 +684,175 @@ fn add_command(
     Ok(())
 }
 
+/// Fills the edit popups with note `index` (0-based), so its title and
+/// body can be changed rather than retyped.
+fn start_edit(
+    notes: &[NoteFormat],
+    index: usize,
+    edit_line_num: &mut TextInput,
+    title: &mut TextInput,
+    body: &mut TextInput,
+) {
+    *edit_line_num = TextInput::new((index + 1).to_string());
+    *title = TextInput::new(notes[index].text.clone());
+    *body = TextInput::new(notes[index].body.clone());
+}
+
 fn edit_line_input(
     f: &mut Frame,
     edit_popup_active: &mut i8,
-    notes: &mut Vec<NoteFormat>,
-    items: &mut Vec<ListItem>,
-    note: &mut NoteFormat,
+    notes: &[NoteFormat],
+    title: &mut TextInput,
+    body: &mut TextInput,
     key_event: KeyEvent,
     action: &mut bool,
     line_cnt: u32,
     area: Rect,
-    edit_line_num: &mut String,
+    edit_line_num: &mut TextInput,
+    toasts: &mut Toasts,
 ) {
-    //TODO: when it is started, edit_line_num is set 0
-    let block = Block::default()
-        .title("Edit note line number")
-        .borders(Borders::ALL);
-    let paragraph = Paragraph::new(edit_line_num.as_str()).block(block);
-    f.render_widget(paragraph, area);
     match key_event.code {
-        KeyCode::Enter => {
-            let edit_line_num: u32 = edit_line_num.parse().unwrap_or(0);
-            if line_cnt >= edit_line_num as u32 {
+        _ if edit_line_num.handle(key_event) => {}
+        KeyCode::Enter => match edit_line_num.value().trim().parse::<usize>() {
+            Ok(n) if n >= 1 && n <= (line_cnt as usize).min(notes.len()) => {
+                start_edit(notes, n - 1, edit_line_num, title, body);
                 *edit_popup_active = 2;
-            } else {
-                *edit_popup_active = 0;
-                *action = false;
             }
-        }
+            _ => toasts.error(format!(
+                "No note \"{}\" (1-{})",
+                edit_line_num.value().trim(),
+                notes.len()
+            )),
+        },
         KeyCode::Esc => {
             *edit_popup_active = 0;
             *action = false;
-            edit_line_num.clear(); //TODO: The variable EDIT_LINE_NUM is not clean.
-        }
-        KeyCode::Backspace => {
-            edit_line_num.pop();
-        }
-        KeyCode::Char(c) => {
-            edit_line_num.push(c);
+            edit_line_num.clear();
         }
         _ => {}
     }
+    if *edit_popup_active != 1 {
+        return;
+    }
+    let block = popup_block()
+        .title("Edit note line number")
+        .borders(Borders::ALL);
+    edit_line_num.render(f, area, block);
 }
+
 fn edit_text_input(
     f: &mut Frame,
     edit_popup_active: &mut i8,
-    notes: &mut Vec<NoteFormat>,
-    items: &mut Vec<ListItem>,
-    note: &mut NoteFormat,
+    title: &mut TextInput,
+    body: &mut TextInput,
     key_event: KeyEvent,
     action: &mut bool,
-    line_cnt: u32,
     area: Rect,
-    edit_line_num: &mut String,
+    toasts: &mut Toasts,
 ) {
-    let line_num = match edit_line_num.trim().parse::<usize>() {
-        Ok(n) if n >= 1 && n <= line_cnt as usize => n - 1,
-        _ => {
-            *edit_popup_active = 0;
-            *action = false;
-            return;
-        }
-    };
-    let selected_note: Option<&NoteFormat> = if line_num < notes.len() {
-        Some(&notes[line_num])
-    } else {
-        // eprintln!(
-        //     "line_num {} is out of range for notes length {}",
-        //     line_num,
-        //     notes.len()
-        // );
-
-        *edit_line_num = String::new();
-        *edit_popup_active = 1;
-        *action = false;
-        None
-    };
-
-    let block = Block::default()
-        .title("Edit note title")
-        .borders(Borders::ALL);
-
-    let paragraph = Paragraph::new(note.text.as_str())
-        .block(block)
-        .wrap(Wrap { trim: true })
-        .alignment(Alignment::Left);
-    f.render_widget(paragraph, area);
-
     match key_event.code {
-        KeyCode::Enter => {
-            if !note.text.trim().is_empty() && note.text.len() <= 101 {
-                //TODO: add error message
-                *edit_popup_active = 3;
-            } else {
-                *edit_popup_active = 0;
-                *action = false;
-            }
-        }
+        _ if title.handle(key_event) => {}
+        KeyCode::Enter => match line_error("Title", title.value()) {
+            Some(err) => toasts.error(err),
+            None => *edit_popup_active = 3,
+        },
         KeyCode::Esc => {
             *action = false;
             *edit_popup_active = 0;
-            note.text.clear();
-        }
-        KeyCode::Backspace => {
-            note.text.pop();
-        }
-        KeyCode::Char(c) => {
-            note.text.push(c);
-        }
-        KeyCode::Right => {
-            if let Some(sn) = selected_note {
-                note.text = sn.text.clone();
-            }
+            title.clear();
+            body.clear();
         }
-
         _ => {}
     }
+    if *edit_popup_active != 2 {
+        return;
+    }
+    let block = popup_block()
+        .title("Edit note title")
+        .title_bottom(length_hint(title.value()))
+        .borders(Borders::ALL);
+    title.render(f, area, block);
 }
 
 fn edit_body_input(
     f: &mut Frame,
     edit_popup_active: &mut i8,
-    notes: &mut Vec<NoteFormat>,
-    items: &mut Vec<ListItem>,
-    note: &mut NoteFormat,
+    notes: &mut [NoteFormat],
+    title: &mut TextInput,
+    body: &mut TextInput,
     key_event: KeyEvent,
     action: &mut bool,
     line_cnt: u32,
     area: Rect,
-    edit_line_num: &mut String,
+    edit_line_num: &mut TextInput,
+    renamed: &mut Option<(String, String)>,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
 ) -> std::io::Result<()> {
-    let mut line_num = match edit_line_num.trim().parse::<usize>() {
-        Ok(n) if n >= 1 && n <= line_cnt as usize => n - 1,
+    let line_num = match edit_line_num.value().trim().parse::<usize>() {
+        Ok(n) if n >= 1 && n <= (line_cnt as usize).min(notes.len()) => n - 1,
         _ => {
             *edit_popup_active = 0;
             *action = false;
             return Ok(());
         }
     };
-    let selected_note = &notes[line_num];
-    let block = Block::default()
-        .title("Edit note body")
-        .borders(Borders::ALL);
-
-    let paragraph = Paragraph::new(note.body.as_str()).block(block);
-    f.render_widget(paragraph, area);
     match key_event.code {
+        _ if body.handle(key_event) => {}
         KeyCode::Enter => {
-            //TODO: add error message
-            if note.body.len() <= 101 {
-                notes[line_num].text = note.text.clone();
-                notes[line_num].body = note.body.clone();
-
-                let mut file = std::fs::OpenOptions::new().write(true).open(file_path)?;
-                let offset = (101 * line_num) as u64;
-                file.seek(std::io::SeekFrom::Start(offset))?;
-                let padded = format!("{:<100}\n", note.text);
-                file.write_all(padded.as_bytes())?;
-                let offset = (101 * (line_num + 1)) as u64;
-                file.seek(std::io::SeekFrom::Start(offset))?;
-                let padded = format!("{:<100}\n", note.body);
-                file.write_all(padded.as_bytes())?;
-
-                items[line_num] = ListItem::new(format!(
-                    "{}: \"{}\" - \"{}\"",
-                    line_num + 1,
-                    notes[line_num].text,
-                    notes[line_num].body
-                ));
+            if let Some(err) = line_error("Body", body.value()) {
+                toasts.error(err);
+            } else {
+                let old_title = notes[line_num].text.clone();
+                if !links::same_title(&old_title, title.value())
+                    && !links::backlinks(notes, line_num).is_empty()
+                {
+                    *renamed = Some((old_title, title.value().to_string()));
+                }
+                // NOTE: on failure the popup stays open, so nothing typed is lost
+                update_note(notes, line_num, undo_history, |note| {
+                    note.text = title.value().to_string();
+                    note.body = body.value().to_string();
+                })?;
+                title.clear();
+                body.clear();
+                edit_line_num.clear();
                 *edit_popup_active = 0;
                 *action = false;
-                note.text.clear();
-                note.body.clear();
-                edit_line_num.clear();
+                toasts.info(format!("Saved note {}", line_num + 1));
+                return Ok(());
             }
         }
         KeyCode::Esc => {
             *edit_popup_active = 0;
             *action = false;
-            note.body.clear();
-        }
-        KeyCode::Backspace => {
-            note.body.pop();
-        }
-        KeyCode::Char(c) => {
-            note.body.push(c);
-        }
-        KeyCode::Right => {
-            note.body = selected_note.body.clone();
+            title.clear();
+            body.clear();
+            return Ok(());
         }
         _ => {}
     }
-
+    let block = popup_block()
+        .title("Edit note body")
+        .title_bottom(length_hint(body.value()))
+        .borders(Borders::ALL);
+    body.render(f, area, block);
     Ok(())
 }
 
 fn edit_command(
     f: &mut Frame,
     edit_popup_active: &mut i8,
-    notes: &mut Vec<NoteFormat>,
-    items: &mut Vec<ListItem>,
-    note: &mut NoteFormat,
+    notes: &mut [NoteFormat],
+    title: &mut TextInput,
+    body: &mut TextInput,
     key_event: KeyEvent,
     action: &mut bool,
     line_cnt: u32,
-    edit_line_num: &mut String,
+    edit_line_num: &mut TextInput,
+    renamed: &mut Option<(String, String)>,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
 ) -> Result<()> {
     *action = true;
     let area = note_title_input(60, 20, f.area());
@@ -432,27 +863,26 @@ fn edit_command(
                 f,
                 edit_popup_active,
                 notes,
-                items,
-                note,
+                title,
+                body,
                 key_event,
                 action,
                 line_cnt,
                 area,
                 edit_line_num,
+                toasts,
             );
         }
         2 => {
             edit_text_input(
                 f,
                 edit_popup_active,
-                notes,
-                items,
-                note,
+                title,
+                body,
                 key_event,
                 action,
-                line_cnt,
                 area,
-                edit_line_num,
+                toasts,
             );
         }
         3 => {
@@ -460,13 +890,16 @@ fn edit_command(
                 f,
                 edit_popup_active,
                 notes,
-                items,
-                note,
+                title,
+                body,
                 key_event,
                 action,
                 line_cnt,
                 text_area,
                 edit_line_num,
+                renamed,
+                undo_history,
+                toasts,
             )?;
         }
         _ => {}
@@ -477,13 +910,16 @@ fn edit_command(
 fn edit_from_list(
     f: &mut Frame,
     edit_from_list_active: &mut i8,
-    notes: &mut Vec<NoteFormat>,
-    items: &mut Vec<ListItem>,
-    note: &mut NoteFormat,
+    notes: &mut [NoteFormat],
+    title: &mut TextInput,
+    body: &mut TextInput,
     key_event: KeyEvent,
     action: &mut bool,
     line_cnt: u32,
-    edit_line_num: &mut String,
+    edit_line_num: &mut TextInput,
+    renamed: &mut Option<(String, String)>,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
 ) -> Result<()> {
     *action = true;
     let area = note_title_input(60, 20, f.area());
@@ -493,14 +929,12 @@ fn edit_from_list(
             edit_text_input(
                 f,
                 edit_from_list_active,
-                notes,
-                items,
-                note,
+                title,
+                body,
                 key_event,
                 action,
-                line_cnt,
                 area,
-                edit_line_num,
+                toasts,
             );
         }
         3 => {
@@ -508,13 +942,16 @@ fn edit_from_list(
                 f,
                 edit_from_list_active,
                 notes,
-                items,
-                note,
+                title,
+                body,
                 key_event,
                 action,
                 line_cnt,
                 text_area,
                 edit_line_num,
+                renamed,
+                undo_history,
+                toasts,
             )?;
         }
         _ => {}
@@ -522,171 +959,2070 @@ fn edit_from_list(
     Ok(())
 }
 
-fn find_command() {}
+fn due_date_input(
+    f: &mut Frame,
+    due_popup_active: &mut bool,
+    notes: &mut [NoteFormat],
+    due_input: &mut TextInput,
+    selected: usize,
+    key_event: KeyEvent,
+    action: &mut bool,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
+) -> Result<()> {
+    *action = true;
+    let handled = due_input.handle(key_event);
+    let area = note_title_input(60, 20, f.area());
+    let parsed = dateparse::parse(due_input.value(), Local::now().naive_local());
+    let preview = if due_input.value().trim().is_empty() {
+        Line::from(" no due date ")
+    } else {
+        match parsed {
+            Some(due) => Line::from(format!(" {} {} ", due.date.format("%a"), due)),
+            None => Line::styled(" not a date ", theme::current().error),
+        }
+    };
+    let block = popup_block()
+        .title("Due date (tomorrow 9am, next fri, 2026-11-02, 明日...)")
+        .title_bottom(preview.right_aligned())
+        .borders(Borders::ALL);
+    due_input.render(f, area, block);
 
-fn filter_command() {}
+    match key_event.code {
+        _ if handled => {}
+        KeyCode::Enter => {
+            let due = if due_input.value().trim().is_empty() {
+                None
+            } else {
+                match parsed {
+                    Some(due) => Some(due),
+                    None => {
+                        toasts.error(format!("Could not read the date \"{}\"", due_input.value()));
+                        return Ok(());
+                    }
+                }
+            };
+            if selected < notes.len() {
+                update_note(notes, selected, undo_history, |note| note.due = due)?;
+            }
+            *due_popup_active = false;
+            *action = false;
+            due_input.clear();
+        }
+        KeyCode::Esc => {
+            *due_popup_active = false;
+            *action = false;
+            due_input.clear();
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn recurrence_input(
+    f: &mut Frame,
+    recur_popup_active: &mut bool,
+    notes: &mut [NoteFormat],
+    recur_input: &mut TextInput,
+    selected: usize,
+    key_event: KeyEvent,
+    action: &mut bool,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
+) -> Result<()> {
+    *action = true;
+    let handled = recur_input.handle(key_event);
+    let area = note_title_input(60, 20, f.area());
+    let today = Local::now().date_naive();
+    let anchor = notes
+        .get(selected)
+        .and_then(|note| note.due)
+        .map_or(today, |due| due.date);
+    let parsed = Recurrence::parse(recur_input.value(), anchor);
+    let preview = if recur_input.value().trim().is_empty() {
+        Line::from(" does not repeat ")
+    } else {
+        match &parsed {
+            Some(recur) => match recur.roll(anchor, today) {
+                Some(next) => {
+                    Line::from(format!(" {}, next {} ", recur, next.format("%a %Y-%m-%d")))
+                }
+                None => Line::from(format!(" {}, no next date ", recur)),
+            },
+            None => Line::styled(" not a rule ", theme::current().error),
+        }
+    };
+    let block = popup_block()
+        .title("Repeat (daily, every 3 days, weekly mon,thu, monthly 15)")
+        .title_bottom(preview.right_aligned())
+        .borders(Borders::ALL);
+    recur_input.render(f, area, block);
+
+    match key_event.code {
+        _ if handled => {}
+        KeyCode::Enter => {
+            let recur = if recur_input.value().trim().is_empty() {
+                None
+            } else {
+                match parsed {
+                    Some(recur) => Some(recur),
+                    None => {
+                        toasts.error(format!(
+                            "Could not read the repeat rule \"{}\"",
+                            recur_input.value()
+                        ));
+                        return Ok(());
+                    }
+                }
+            };
+            if selected < notes.len() {
+                update_note(notes, selected, undo_history, |note| {
+                    if recur.is_some() && note.due.is_none() {
+                        note.due = Some(Due {
+                            date: anchor,
+                            time: None,
+                        });
+                    }
+                    note.recur = recur;
+                })?;
+            }
+            *recur_popup_active = false;
+            *action = false;
+            recur_input.clear();
+        }
+        KeyCode::Esc => {
+            *recur_popup_active = false;
+            *action = false;
+            recur_input.clear();
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn move_input(
+    f: &mut Frame,
+    move_popup_active: &mut bool,
+    notes: &mut [NoteFormat],
+    folder_input: &mut TextInput,
+    selected: usize,
+    key_event: KeyEvent,
+    action: &mut bool,
+    undo_history: &mut UndoHistory,
+) -> Result<()> {
+    *action = true;
+    let handled = folder_input.handle(key_event);
+    let area = note_title_input(60, 20, f.area());
+    let block = popup_block()
+        .title("Move to folder (e.g. work/infra, empty for top level)")
+        .borders(Borders::ALL);
+    folder_input.render(f, area, block);
+
+    match key_event.code {
+        _ if handled => {}
+        KeyCode::Enter => {
+            if selected < notes.len() {
+                let folder = tree::normalize(folder_input.value());
+                update_note(notes, selected, undo_history, |note| note.folder = folder)?;
+            }
+            *move_popup_active = false;
+            *action = false;
+            folder_input.clear();
+        }
+        KeyCode::Esc => {
+            *move_popup_active = false;
+            *action = false;
+            folder_input.clear();
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn fields_input(
+    f: &mut Frame,
+    fields_popup_active: &mut bool,
+    notes: &mut [NoteFormat],
+    field_input: &mut TextInput,
+    selected: usize,
+    key_event: KeyEvent,
+    action: &mut bool,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
+) -> Result<()> {
+    *action = true;
+    if selected >= notes.len() {
+        *fields_popup_active = false;
+        *action = false;
+        return Ok(());
+    };
+
+    match key_event.code {
+        _ if field_input.handle(key_event) => {}
+        KeyCode::Enter if field_input.value().trim().is_empty() => {
+            *fields_popup_active = false;
+            *action = false;
+            return Ok(());
+        }
+        KeyCode::Enter => match fields::parse_entry(field_input.value()) {
+            Some((key, value)) => {
+                update_note(notes, selected, undo_history, |note| {
+                    if value.is_empty() {
+                        note.fields.remove(&key);
+                    } else {
+                        note.fields.set(&key, &value);
+                    }
+                })?;
+                field_input.clear();
+            }
+            None => toasts.error("Expected \"key: value\", with no spaces in the key"),
+        },
+        KeyCode::Esc => {
+            *fields_popup_active = false;
+            *action = false;
+            field_input.clear();
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // the fields so far, with the new entry typed on the last line
+    let lines: Vec<Line> = notes[selected]
+        .fields
+        .iter()
+        .map(|(key, value)| Line::from(format!("{}: {}", key, value)))
+        .collect();
+    let area = note_body_input(60, 20, f.area());
+    let block = popup_block()
+        .title("Fields (key: value, empty value removes, Enter on empty line closes)")
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    let input_y = inner.y + (lines.len() as u16).min(inner.height.saturating_sub(1));
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+    field_input.render_prefixed(
+        f,
+        Rect::new(inner.x, input_y, inner.width, 1),
+        Block::new(),
+        "> ",
+    );
+    Ok(())
+}
+
+fn label_picker(
+    f: &mut Frame,
+    label_popup_active: &mut bool,
+    notes: &mut [NoteFormat],
+    selected: usize,
+    label_state: &mut ListState,
+    key_event: KeyEvent,
+    action: &mut bool,
+    undo_history: &mut UndoHistory,
+) -> Result<()> {
+    *action = true;
+    let mut items = vec![ListItem::new("  none")];
+    items.extend(Label::ALL.iter().map(|&label| {
+        ListItem::new(Line::from(vec![
+            Span::styled("■ ", theme::current().label(label)),
+            Span::raw(label.to_string()),
+        ]))
+    }));
+    let area = note_body_input(30, 30, f.area());
+    let list = List::new(items)
+        .block(popup_block().title("Color label").borders(Borders::ALL))
+        .highlight_symbol(">> ")
+        .highlight_style(theme::current().selected_label);
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, label_state);
+
+    let count = Label::ALL.len() + 1;
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            let i = label_state.selected().unwrap_or(0);
+            label_state.select(Some((i + 1) % count));
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            let i = label_state.selected().unwrap_or(0);
+            label_state.select(Some(if i == 0 { count - 1 } else { i - 1 }));
+        }
+        KeyCode::Enter => {
+            if selected < notes.len() {
+                let label = label_state
+                    .selected()
+                    .unwrap_or(0)
+                    .checked_sub(1)
+                    .map(|i| Label::ALL[i]);
+                update_note(notes, selected, undo_history, |note| note.label = label)?;
+            }
+            *label_popup_active = false;
+            *action = false;
+        }
+        KeyCode::Esc => {
+            *label_popup_active = false;
+            *action = false;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn attachment_item(path: &str) -> ListItem<'static> {
+    if attach::is_missing(path) {
+        ListItem::new(format!("{} (missing)", path)).style(theme::current().error)
+    } else {
+        ListItem::new(path.to_string())
+    }
+}
+
+/// Attachments of the selected note. 1 lists them, 2 asks for a new path.
+fn attachments_view(
+    f: &mut Frame,
+    attach_popup_active: &mut i8,
+    notes: &mut [NoteFormat],
+    selected: usize,
+    attach_state: &mut ListState,
+    attach_input: &mut TextInput,
+    key_event: KeyEvent,
+    action: &mut bool,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
+) -> Result<()> {
+    *action = true;
+    let Some(note) = notes.get(selected) else {
+        *attach_popup_active = 0;
+        *action = false;
+        return Ok(());
+    };
+    let items: Vec<ListItem> = note
+        .attachments
+        .iter()
+        .map(|path| attachment_item(path))
+        .collect();
+    let area = note_body_input(60, 20, f.area());
+    let list = List::new(items)
+        .block(
+            popup_block()
+                .title("Attachments (Enter/y : copy path | a : attach | d : remove)")
+                .borders(Borders::ALL),
+        )
+        .highlight_symbol(">> ")
+        .highlight_style(theme::current().selected);
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, attach_state);
+
+    if *attach_popup_active == 2 {
+        let handled = attach_input.handle(key_event);
+        let area = note_title_input(60, 20, f.area());
+        let block = popup_block()
+            .title("Attach file (path)")
+            .borders(Borders::ALL);
+        attach_input.render(f, area, block);
+        match key_event.code {
+            _ if handled => {}
+            KeyCode::Enter => {
+                if !attach_input.value().trim().is_empty() {
+                    let path = attach::absolute(attach_input.value()).display().to_string();
+                    if attach::is_missing(&path) {
+                        toasts.error(format!("Attached {}, which does not exist yet", path));
+                    } else {
+                        toasts.info(format!("Attached {}", path));
+                    }
+                    let count = note.attachments.len();
+                    update_note(notes, selected, undo_history, |note| {
+                        if !note.attachments.contains(&path) {
+                            note.attachments.push(path);
+                        }
+                    })?;
+                    attach_state.select(Some(notes[selected].attachments.len().max(count) - 1));
+                }
+                attach_input.clear();
+                *attach_popup_active = 1;
+            }
+            KeyCode::Esc => {
+                attach_input.clear();
+                *attach_popup_active = 1;
+            }
+            _ => {}
+        }
+        return Ok(());
+    }
+
+    let count = note.attachments.len();
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+            let i = attach_state.selected().unwrap_or(0);
+            attach_state.select(Some((i + 1) % count));
+        }
+        KeyCode::Up | KeyCode::Char('k') if count > 0 => {
+            let i = attach_state.selected().unwrap_or(0);
+            attach_state.select(Some(if i == 0 { count - 1 } else { i - 1 }));
+        }
+        KeyCode::Enter | KeyCode::Char('y') => {
+            if let Some(path) = attach_state
+                .selected()
+                .and_then(|i| note.attachments.get(i))
+            {
+                *attach_popup_active = 0;
+                *action = false;
+                if toasts
+                    .report(clipboard::copy(path), "Could not copy the path")
+                    .is_some()
+                {
+                    toasts.info(format!("Copied {}", path));
+                }
+            }
+        }
+        KeyCode::Char('a') => *attach_popup_active = 2,
+        KeyCode::Char('d') => {
+            if let Some(i) = attach_state.selected().filter(|&i| i < count) {
+                update_note(notes, selected, undo_history, |note| {
+                    note.attachments.remove(i);
+                })?;
+                attach_state.select(Some(i.min(count.saturating_sub(2))));
+            }
+        }
+        KeyCode::Esc => {
+            *attach_popup_active = 0;
+            *action = false;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// `text` as spans with the characters at `positions` highlighted.
+fn highlight_chars(text: &str, positions: &[usize]) -> Vec<Span<'static>> {
+    let style = theme::current().fuzzy_match;
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if positions.contains(&i) {
+                Span::styled(c.to_string(), style)
+            } else {
+                Span::raw(c.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Lines describing `note` for the fuzzy finder preview.
+fn note_preview(note: &NoteFormat) -> Vec<Line<'static>> {
+    let mut lines = vec![
+        Line::styled(
+            note.text.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Line::default(),
+        Line::from(note.body.clone()),
+        Line::default(),
+    ];
+    let dim = theme::current().hint;
+    if !note.folder.is_empty() {
+        lines.push(Line::styled(format!("folder: {}", note.folder), dim));
+    }
+    if let Some(due) = note.due {
+        lines.push(Line::styled(format!("due: {}", due), dim));
+    }
+    if note.priority != Priority::None {
+        lines.push(Line::styled(format!("priority: {}", note.priority), dim));
+    }
+    if note.archived {
+        lines.push(Line::styled("archived", dim));
+    }
+    for (key, value) in note.fields.iter() {
+        lines.push(Line::styled(format!("{}: {}", key, value), dim));
+    }
+    for path in &note.attachments {
+        lines.push(Line::styled(format!("attached: {}", path), dim));
+    }
+    lines
+}
+
+fn fuzzy_finder(
+    f: &mut Frame,
+    fuzzy_active: &mut bool,
+    notes: &[NoteFormat],
+    view: &[usize],
+    fuzzy_input: &mut TextInput,
+    fuzzy_state: &mut ListState,
+    jump_to: &mut Option<usize>,
+    edit_line_num: &mut TextInput,
+    title: &mut TextInput,
+    body: &mut TextInput,
+    edit_from_list_active: &mut i8,
+    key_event: KeyEvent,
+    action: &mut bool,
+) {
+    *action = true;
+    let ctrl = |c: char| {
+        key_event.code == KeyCode::Char(c)
+            && key_event.modifiers.contains(event::KeyModifiers::CONTROL)
+    };
+    // Ctrl-J/K/N/P pick a result, so they are not passed to the input
+    let before = fuzzy_input.value().to_string();
+    let handled =
+        !(ctrl('j') || ctrl('k') || ctrl('n') || ctrl('p')) && fuzzy_input.handle(key_event);
+    if fuzzy_input.value() != before {
+        fuzzy_state.select(Some(0));
+    }
+    let hits = fuzzy::rank(notes, view, fuzzy_input.value());
+    let selected = fuzzy_state.selected().unwrap_or(0);
+    if key_event.code == KeyCode::Down || ctrl('j') || ctrl('n') {
+        fuzzy_state.select(Some((selected + 1).min(hits.len().saturating_sub(1))));
+    } else if key_event.code == KeyCode::Up || ctrl('k') || ctrl('p') {
+        fuzzy_state.select(Some(selected.saturating_sub(1)));
+    }
+
+    let area = agenda_popup(f.area());
+    let [input_area, results_area] =
+        Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(area);
+    let [list_area, preview_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .areas(results_area);
+    let input_block = popup_block()
+        .title(format!(
+            "Find note ({}/{}) - Enter : go to | Tab : edit | Esc : close",
+            hits.len(),
+            view.len()
+        ))
+        .border_type(BorderType::Rounded)
+        .borders(Borders::ALL);
+    let items: Vec<ListItem> = hits
+        .iter()
+        .map(|hit| {
+            let note = &notes[hit.index];
+            let mut spans = vec![Span::raw(format!("{}: ", hit.index + 1))];
+            spans.extend(highlight_chars(&note.text, &hit.title));
+            spans.push(Span::raw(" - "));
+            spans.extend(highlight_chars(&note.body, &hit.body));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(
+            popup_block()
+                .border_type(BorderType::Rounded)
+                .borders(Borders::ALL),
+        )
+        .highlight_symbol(">> ")
+        .highlight_style(theme::current().selected);
+    let current = fuzzy_state
+        .selected()
+        .and_then(|i| hits.get(i))
+        .map(|hit| hit.index);
+    let preview = Paragraph::new(current.map(|i| note_preview(&notes[i])).unwrap_or_default())
+        .block(
+            popup_block()
+                .title("Preview")
+                .border_type(BorderType::Rounded)
+                .borders(Borders::ALL),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    fuzzy_input.render_prefixed(f, input_area, input_block, "> ");
+    f.render_stateful_widget(list, list_area, fuzzy_state);
+    f.render_widget(preview, preview_area);
+
+    match key_event.code {
+        _ if handled => {}
+        KeyCode::Enter | KeyCode::Tab => {
+            if let Some(index) = current {
+                *jump_to = Some(index);
+                if key_event.code == KeyCode::Tab {
+                    start_edit(notes, index, edit_line_num, title, body);
+                    *edit_from_list_active = 2; // NOTE: same as Enter in the list
+                }
+                *fuzzy_active = false;
+                *action = false;
+                fuzzy_input.clear();
+            }
+        }
+        KeyCode::Esc => {
+            *fuzzy_active = false;
+            *action = false;
+            fuzzy_input.clear();
+        }
+        _ => {}
+    }
+}
+
+/// Makes `change` to the notes, writes the notebook and records it for undo.
+/// `change` must not add or remove notes. If writing fails the notes are
+/// left as they were.
+fn update_notes(
+    notes: &mut [NoteFormat],
+    undo_history: &mut UndoHistory,
+    change: impl FnOnce(&mut [NoteFormat]),
+) -> io::Result<()> {
+    let before = notes.to_vec();
+    change(notes);
+    let edits: Vec<_> = (0..notes.len())
+        .filter(|&i| notes[i] != before[i])
+        .map(|i| (i, before[i].clone(), notes[i].clone()))
+        .collect();
+    if edits.is_empty() {
+        return Ok(());
+    }
+    let retitled = edits
+        .iter()
+        .any(|(_, old, new)| old.text != new.text || old.body != new.body);
+    let saved = if retitled {
+        save_notes(notes)
+    } else {
+        save_meta(notes)
+    };
+    if let Err(err) = saved {
+        notes.clone_from_slice(&before);
+        return Err(err);
+    }
+    undo_history.record(Change::Edited(edits));
+    Ok(())
+}
+
+/// [`update_notes`] for a change to note `index` alone.
+fn update_note(
+    notes: &mut [NoteFormat],
+    index: usize,
+    undo_history: &mut UndoHistory,
+    change: impl FnOnce(&mut NoteFormat),
+) -> io::Result<()> {
+    update_notes(notes, undo_history, |notes| change(&mut notes[index]))
+}
+
+/// Note numbers as the TUI shows them, such as `2, 5`.
+fn numbers(indices: &[usize]) -> String {
+    let numbers: Vec<String> = indices.iter().map(|i| (i + 1).to_string()).collect();
+    numbers.join(", ")
+}
+
+/// Deletes note `index`, keeping it for undo, and writes the notebook.
+fn delete_note(
+    notes: &mut Vec<NoteFormat>,
+    index: usize,
+    history: [&mut Vec<usize>; 2],
+    line_cnt: &mut u32,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
+) -> io::Result<()> {
+    let (change, dropped) = Change::delete(notes, index);
+    if let Err(err) = save_notes(notes) {
+        change.revert(notes);
+        return Err(err);
+    }
+    *line_cnt = notes.len() as u32;
+    undo_history.record(change);
+    // back/forward entries point into `notes`, so shift them too
+    for stack in history {
+        stack.retain(|&i| i != index);
+        for i in stack.iter_mut() {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+    }
+    if dropped.is_empty() {
+        toasts.info(format!("Deleted note {}", index + 1));
+    } else {
+        toasts.error(format!(
+            "Deleted note {}; its title didn't fit in note {}, so the links there were removed",
+            index + 1,
+            numbers(&dropped)
+        ));
+    }
+    Ok(())
+}
+
+fn delete_confirm(
+    f: &mut Frame,
+    delete_active: &mut bool,
+    notes: &mut Vec<NoteFormat>,
+    selected: usize,
+    history: [&mut Vec<usize>; 2],
+    line_cnt: &mut u32,
+    key_event: KeyEvent,
+    action: &mut bool,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
+) -> Result<()> {
+    *action = true;
+    let Some(note) = notes.get(selected) else {
+        *delete_active = false;
+        *action = false;
+        return Ok(());
+    };
+    let area = note_body_input(60, 20, f.area());
+    let block = popup_block()
+        .title("Delete note? (y/n)")
+        .borders(Borders::ALL)
+        .border_style(theme::current().error);
+    // the zsh script and `note-rust` commands count from 0
+    let paragraph = Paragraph::new(vec![
+        Line::from(format!(
+            "{}: \"{}\" - \"{}\"",
+            selected + 1,
+            note.text,
+            note.body
+        )),
+        Line::default(),
+        Line::styled(
+            format!("This is `note del {}` on the command line.", selected),
+            theme::current().hint,
+        ),
+    ])
+    .block(block)
+    .wrap(Wrap { trim: true });
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+
+    match key_event.code {
+        KeyCode::Char('y') | KeyCode::Char('Y') => {
+            *delete_active = false;
+            *action = false;
+            delete_note(notes, selected, history, line_cnt, undo_history, toasts)?;
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+            *delete_active = false;
+            *action = false;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Undoes the last `count` changes, such as an add, an edit, a delete or a
+/// renamed link (or redoes the last undone ones), and writes the notebook.
+/// Returns the note to select.
+fn undo_change(
+    notes: &mut Vec<NoteFormat>,
+    undo_history: &mut UndoHistory,
+    redo: bool,
+    count: usize,
+    history: [&mut Vec<usize>; 2],
+    toasts: &mut Toasts,
+) -> Option<usize> {
+    // up to `count` changes, fewer when the history runs out
+    let mut changes = vec![];
+    for _ in 0..count {
+        let change = if redo {
+            undo_history.redo(notes)
+        } else {
+            undo_history.undo(notes)
+        };
+        let Some(change) = change else {
+            break;
+        };
+        changes.push((change.index(), change.describe(), change.renumbers()));
+    }
+    let Some((index, what, _)) = changes.last().cloned() else {
+        toasts.info(if redo {
+            "Nothing to redo"
+        } else {
+            "Nothing to undo"
+        });
+        return None;
+    };
+    if let Err(err) = save_notes(notes) {
+        // put them back so the history still matches the notebook on disk
+        for _ in &changes {
+            if redo {
+                undo_history.undo(notes);
+            } else {
+                undo_history.redo(notes);
+            }
+        }
+        toasts.error(format!("Could not save: {}", err));
+        return None;
+    }
+    if changes.iter().any(|&(_, _, renumbers)| renumbers) {
+        // back/forward entries point into `notes`, which just moved
+        for stack in history {
+            stack.clear();
+        }
+    }
+    let what = match changes.len() {
+        1 => what,
+        n => format!("{} changes", n),
+    };
+    toasts.info(format!("{} {}", if redo { "Redid" } else { "Undid" }, what));
+    (index < notes.len()).then_some(index)
+}
+
+fn link_picker(
+    f: &mut Frame,
+    link_picker_active: &mut bool,
+    notes: &[NoteFormat],
+    selected: usize,
+    picker_state: &mut ListState,
+    jump_to: &mut Option<usize>,
+    key_event: KeyEvent,
+    action: &mut bool,
+    toasts: &mut Toasts,
+) {
+    *action = true;
+    let targets = notes
+        .get(selected)
+        .map(|note| links::targets(&note.body))
+        .unwrap_or_default();
+    let items: Vec<ListItem> = targets
+        .iter()
+        .map(|target| match links::resolve(target, notes) {
+            Some(i) => ListItem::new(format!("[[{}]] → {}: \"{}\"", target, i + 1, notes[i].text)),
+            None => {
+                ListItem::new(format!("[[{}]] (no such note)", target)).style(theme::current().hint)
+            }
+        })
+        .collect();
+    let area = note_body_input(60, 20, f.area());
+    let list = List::new(items)
+        .block(popup_block().title("Follow link").borders(Borders::ALL))
+        .highlight_symbol(">> ")
+        .highlight_style(theme::current().selected);
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, picker_state);
+
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            let i = picker_state.selected().unwrap_or(0);
+            picker_state.select(Some((i + 1) % targets.len().max(1)));
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            let i = picker_state.selected().unwrap_or(0);
+            picker_state.select(Some(if i == 0 {
+                targets.len().saturating_sub(1)
+            } else {
+                i - 1
+            }));
+        }
+        KeyCode::Enter => {
+            if let Some(target) = picker_state.selected().and_then(|i| targets.get(i)) {
+                match links::resolve(target, notes) {
+                    Some(index) => {
+                        *jump_to = Some(index);
+                        *link_picker_active = false;
+                        *action = false;
+                    }
+                    None => toasts.error(format!("No note matches [[{}]]", target)),
+                }
+            }
+        }
+        KeyCode::Esc => {
+            *link_picker_active = false;
+            *action = false;
+        }
+        _ => {}
+    }
+}
+
+fn rename_links_prompt(
+    f: &mut Frame,
+    renamed: &mut Option<(String, String)>,
+    notes: &mut [NoteFormat],
+    key_event: KeyEvent,
+    action: &mut bool,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
+) -> Result<()> {
+    let Some((old_title, new_title)) = renamed.clone() else {
+        return Ok(());
+    };
+    *action = true;
+    // bodies that would get longer than a line keep the old link
+    let (relinked, too_long): (Vec<_>, Vec<_>) = notes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, note)| Some((i, links::retarget(&note.body, &old_title, &new_title)?)))
+        .partition(|(_, body)| body.chars().count() <= MAX_LINE);
+    let too_long: Vec<usize> = too_long.into_iter().map(|(i, _)| i).collect();
+    let area = note_title_input(60, 20, f.area());
+    let block = popup_block()
+        .title("Update links? (y/n)")
+        .borders(Borders::ALL);
+    let mut text = format!(
+        "{} note(s) link to [[{}]]. Point them at [[{}]]?",
+        relinked.len() + too_long.len(),
+        old_title,
+        new_title
+    );
+    if !too_long.is_empty() {
+        text.push_str(&format!(
+            " Note {} would get too long and keeps the old link.",
+            numbers(&too_long)
+        ));
+    }
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+
+    match key_event.code {
+        KeyCode::Char('y') | KeyCode::Char('Y') => {
+            *renamed = None;
+            *action = false;
+            let count = relinked.len();
+            update_notes(notes, undo_history, |notes| {
+                for (i, body) in relinked {
+                    notes[i].body = body;
+                }
+            })?;
+            if too_long.is_empty() {
+                toasts.info(format!("Updated the links in {} notes", count));
+            } else {
+                toasts.error(format!(
+                    "Updated the links in {} notes; note {} would get too long and keeps [[{}]]",
+                    count,
+                    numbers(&too_long),
+                    old_title
+                ));
+            }
+        }
+        KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+            *renamed = None;
+            *action = false;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// A review session over the notes that were due when it started.
+struct Review {
+    queue: Vec<usize>,
+    pos: usize,
+    revealed: bool,
+}
+
+fn review_view(
+    f: &mut Frame,
+    review: &mut Option<Review>,
+    notes: &mut [NoteFormat],
+    key_event: KeyEvent,
+    action: &mut bool,
+    undo_history: &mut UndoHistory,
+    toasts: &mut Toasts,
+) -> Result<()> {
+    let Some(session) = review else {
+        return Ok(());
+    };
+    *action = true;
+    let Some(&index) = session.queue.get(session.pos) else {
+        *review = None;
+        *action = false;
+        return Ok(());
+    };
+    let today = Local::now().date_naive();
+    let note = &notes[index];
+
+    let mut lines = vec![
+        Line::styled(
+            note.text.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Line::default(),
+    ];
+    if session.revealed {
+        lines.push(Line::from(note.body.clone()));
+        lines.push(Line::default());
+        let grades: Vec<String> = review::GRADES
+            .iter()
+            .map(|&(key, name, grade)| {
+                let card = review::schedule(note.card, grade, today);
+                format!("{} : {} ({}d)", key, name, card.interval)
+            })
+            .collect();
+        lines.push(Line::styled(grades.join(" | "), theme::current().accent));
+    } else {
+        lines.push(Line::styled("Space : show answer", theme::current().hint));
+    }
+
+    let area = agenda_popup(f.area());
+    let block = popup_block()
+        .title(format!(
+            "Review ({}/{})",
+            session.pos + 1,
+            session.queue.len()
+        ))
+        .border_type(BorderType::Rounded)
+        .borders(Borders::ALL);
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+
+    match key_event.code {
+        KeyCode::Char(' ') | KeyCode::Enter if !session.revealed => session.revealed = true,
+        KeyCode::Char(c) if session.revealed => {
+            if let Some(&(_, _, grade)) = review::GRADES.iter().find(|(key, _, _)| *key == c) {
+                let card = review::schedule(notes[index].card, grade, today);
+                update_note(notes, index, undo_history, |note| note.card = Some(card))?;
+                session.pos += 1;
+                session.revealed = false;
+                if session.pos >= session.queue.len() {
+                    toasts.info(format!("Reviewed {} notes", session.queue.len()));
+                    *review = None;
+                    *action = false;
+                }
+            }
+        }
+        KeyCode::Esc | KeyCode::Char('q') => {
+            *review = None;
+            *action = false;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn agenda_view(
+    f: &mut Frame,
+    agenda_active: &mut bool,
+    notes: &[NoteFormat],
+    key_event: KeyEvent,
+    action: &mut bool,
+) {
+    *action = true;
+    let area = agenda_popup(f.area());
+    let now = Local::now().naive_local();
+
+    let mut lines: Vec<Line> = vec![];
+    for (bucket, entries) in agenda::group(notes, now) {
+        let style = match bucket {
+            Bucket::Overdue => theme::current().overdue,
+            Bucket::Today => theme::current().due_today,
+            _ => Style::default(),
+        };
+        lines.push(Line::styled(
+            format!("{} ({})", bucket.label(), entries.len()),
+            style.add_modifier(Modifier::BOLD),
+        ));
+        for (i, note) in entries {
+            let due = note.due.map(|due| due.to_string()).unwrap_or_default();
+            lines.push(Line::from(format!(
+                "  {}: \"{}\" - \"{}\"  [due {}]",
+                i + 1,
+                note.text,
+                note.body,
+                due
+            )));
+        }
+        lines.push(Line::default());
+    }
+
+    let block = popup_block()
+        .title("Agenda")
+        .border_type(BorderType::Rounded)
+        .borders(Borders::ALL);
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+
+    match key_event.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('A') => {
+            *agenda_active = false;
+            *action = false;
+        }
+        _ => {}
+    }
+}
+
+/// Every binding of the keymap, in as many columns as fit.
+fn keys_view(
+    f: &mut Frame,
+    keys_active: &mut bool,
+    keymap: &Keymap,
+    key_event: KeyEvent,
+    action: &mut bool,
+) {
+    *action = true;
+    let area = agenda_popup(f.area());
+    let entries = keymap.help();
+    let width = entries
+        .iter()
+        .map(|entry| entry.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 3;
+    let columns = (area.width.saturating_sub(2) as usize / width).max(1);
+    let rows = entries.len().div_ceil(columns);
+    let lines: Vec<Line> = (0..rows)
+        .map(|row| {
+            let line: String = entries
+                .iter()
+                .skip(row)
+                .step_by(rows)
+                .map(|entry| format!("{:<width$}", entry, width = width))
+                .collect();
+            Line::from(line.trim_end().to_string())
+        })
+        .collect();
+
+    let block = popup_block()
+        .title("Keys - Esc : close")
+        .border_type(BorderType::Rounded)
+        .borders(Borders::ALL);
+    let paragraph = Paragraph::new(lines).block(block);
+    f.render_widget(Clear, area);
+    f.render_widget(paragraph, area);
+
+    match key_event.code {
+        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('?') => {
+            *keys_active = false;
+            *action = false;
+        }
+        _ => {}
+    }
+}
+
+/// The search bar over the footer. Keys are only handled while the query
+/// is being typed; each change jumps to the nearest match.
+fn find_command(
+    f: &mut Frame,
+    search: &mut Search,
+    notes: &[NoteFormat],
+    view: &[usize],
+    list_state: &mut ListState,
+    key_event: KeyEvent,
+    action: &mut bool,
+) {
+    let size = f.area();
+    let area = Rect::new(
+        0,
+        size.height.saturating_sub(3),
+        size.width,
+        3.min(size.height),
+    );
+    let hint = if search.editing {
+        "Enter : done | Tab : scope | Esc : clear"
+    } else {
+        "n/N : next/prev | f : new search | Esc : clear"
+    };
+    let matches = view.iter().filter(|&&i| search.matches(&notes[i])).count();
+    let block = popup_block()
+        .title(format!(
+            "Find in {} ({} matching) - {}",
+            search.scope.label(),
+            matches,
+            hint
+        ))
+        .border_type(BorderType::Rounded)
+        .borders(Borders::ALL)
+        .border_style(theme::current().warning);
+    if !search.editing {
+        let paragraph = Paragraph::new(format!("/{}", search.query.value())).block(block);
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
+        return;
+    }
+    *action = true;
+    let from = list_state.selected().unwrap_or(0);
+    let before = search.query.value().to_string();
+    let handled = search.query.handle(key_event);
+    search.query.render_prefixed(f, area, block, "/");
+    match key_event.code {
+        _ if handled && search.query.value() == before => return,
+        _ if handled => {}
+        KeyCode::Enter => {
+            search.editing = false;
+            *action = false;
+            return;
+        }
+        KeyCode::Esc => {
+            *search = Search::default();
+            *action = false;
+            return;
+        }
+        KeyCode::Tab => search.scope = search.scope.next(),
+        _ => return,
+    }
+    if let Some(pos) = search.step(notes, view, from, true, true) {
+        list_state.select(Some(pos));
+    }
+}
+
+/// The filter bar over the footer. The list narrows as the expression is
+/// typed; Esc puts back the filter that was active before.
+fn filter_command(
+    f: &mut Frame,
+    filter_popup_active: &mut bool,
+    view_options: &mut ViewOptions,
+    filter_input: &mut TextInput,
+    filter_before: &mut Option<Filter>,
+    key_event: KeyEvent,
+    action: &mut bool,
+) {
+    *action = true;
+    match key_event.code {
+        _ if filter_input.handle(key_event) => {}
+        KeyCode::Enter
+            if Filter::parse(filter_input.value(), Local::now().naive_local()).is_ok() =>
+        {
+            *filter_popup_active = false;
+            *action = false;
+            filter_input.clear();
+            return;
+        }
+        KeyCode::Esc => {
+            view_options.filter = filter_before.take();
+            *filter_popup_active = false;
+            *action = false;
+            filter_input.clear();
+            return;
+        }
+        _ => {}
+    }
+
+    let parsed = if filter_input.value().trim().is_empty() {
+        Ok(None)
+    } else {
+        Filter::parse(filter_input.value(), Local::now().naive_local()).map(Some)
+    };
+    let (title, style) = match parsed {
+        Ok(filter) => {
+            view_options.filter = filter;
+            (
+                "Filter (e.g. title:deploy -tag:done created>2026-01-01, empty clears)".to_string(),
+                theme::current().warning,
+            )
+        }
+        Err(err) => (err, theme::current().error),
+    };
+    let size = f.area();
+    let area = Rect::new(
+        0,
+        size.height.saturating_sub(3),
+        size.width,
+        3.min(size.height),
+    );
+    let block = popup_block()
+        .title(title)
+        .border_type(BorderType::Rounded)
+        .borders(Borders::ALL)
+        .border_style(style);
+    filter_input.render(f, area, block);
+}
 
-#[derive(Clone, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 struct NoteFormat {
     text: String,
     body: String,
+    due: Option<Due>,
+    priority: Priority,
+    /// When the note was added. Notes from before this was recorded have none.
+    created: Option<NaiveDateTime>,
+    /// Pinned notes are listed before all others.
+    pinned: bool,
+    /// Archived notes only show up in the Archive panel.
+    archived: bool,
+    /// `/` separated folder path, empty for the top level.
+    folder: String,
+    /// When the note was marked done. Recurring notes never stay done.
+    done: Option<NaiveDateTime>,
+    recur: Option<Recurrence>,
+    /// Every time the note was completed, oldest first.
+    history: Vec<NaiveDateTime>,
+    fields: Fields,
+    label: Option<Label>,
+    /// Absolute paths of attached files.
+    attachments: Vec<String>,
+    /// Spaced-repetition schedule, once the note has been reviewed.
+    card: Option<Card>,
+    /// When the running timer was started.
+    timer: Option<NaiveDateTime>,
+    /// Finished timer sessions, oldest first.
+    sessions: Vec<Session>,
+}
+
+impl NoteFormat {
+    fn meta_line(&self) -> String {
+        let mut pairs: Vec<(&str, String)> = vec![];
+        if let Some(created) = self.created {
+            pairs.push(("created", meta::format_datetime(created)));
+        }
+        if let Some(due) = self.due {
+            pairs.push(("due", due.to_meta()));
+        }
+        if self.priority != Priority::None {
+            pairs.push(("priority", self.priority.to_string()));
+        }
+        if self.pinned {
+            pairs.push(("pinned", "1".to_string()));
+        }
+        if self.archived {
+            pairs.push(("archived", "1".to_string()));
+        }
+        if !self.folder.is_empty() {
+            pairs.push(("folder", self.folder.clone()));
+        }
+        if let Some(done) = self.done {
+            pairs.push(("done", meta::format_datetime(done)));
+        }
+        if let Some(recur) = &self.recur {
+            pairs.push(("recur", recur.to_string()));
+        }
+        if !self.history.is_empty() {
+            let history: Vec<String> = self
+                .history
+                .iter()
+                .map(|dt| meta::format_datetime(*dt))
+                .collect();
+            pairs.push(("history", history.join(",")));
+        }
+        if let Some(label) = self.label {
+            pairs.push(("label", label.to_string()));
+        }
+        for path in &self.attachments {
+            pairs.push(("attach", path.clone()));
+        }
+        if let Some(card) = self.card {
+            pairs.push(("review", card.to_meta()));
+        }
+        if let Some(timer) = self.timer {
+            pairs.push(("timer", timelog::format_datetime(timer)));
+        }
+        if !self.sessions.is_empty() {
+            let sessions: Vec<String> = self.sessions.iter().map(|s| s.to_meta()).collect();
+            pairs.push(("sessions", sessions.join(",")));
+        }
+        let field_keys: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(key, _)| format!("field.{}", key))
+            .collect();
+        for (key, (_, value)) in field_keys.iter().zip(self.fields.iter()) {
+            pairs.push((key, value.to_string()));
+        }
+        meta::format_line(&pairs)
+    }
+
+    fn apply_meta(&mut self, line: &str) {
+        for (key, value) in meta::parse_line(line) {
+            match key.as_str() {
+                "created" => self.created = meta::parse_datetime(&value),
+                "due" => self.due = Due::parse(&value),
+                "priority" => self.priority = Priority::parse(&value).unwrap_or_default(),
+                "pinned" => self.pinned = value == "1",
+                "archived" => self.archived = value == "1",
+                "folder" => self.folder = tree::normalize(&value),
+                "done" => self.done = meta::parse_datetime(&value),
+                "recur" => {
+                    let anchor = self.due.map_or(Local::now().date_naive(), |due| due.date);
+                    self.recur = Recurrence::parse(&value, anchor);
+                }
+                "history" => {
+                    self.history = value.split(',').filter_map(meta::parse_datetime).collect();
+                }
+                "label" => self.label = Label::parse(&value),
+                "attach" => self.attachments.push(value),
+                "review" => self.card = Card::parse(&value),
+                "timer" => self.timer = timelog::parse_datetime(&value),
+                "sessions" => {
+                    self.sessions = value.split(',').filter_map(Session::parse).collect();
+                }
+                key => {
+                    if let Some(field) = key.strip_prefix("field.") {
+                        self.fields.set(field, &value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Starts the timer, or stops it and records the session.
+    fn toggle_timer(&mut self, now: NaiveDateTime) {
+        match self.timer.take() {
+            Some(start) => self.sessions.push(Session { start, end: now }),
+            None => self.timer = Some(now),
+        }
+    }
+
+    /// Marks the note done, or reopens it if it already was. Completing a
+    /// recurring note moves its due date to the next occurrence instead, so
+    /// it stays open; the completion is recorded in `history` either way.
+    fn toggle_done(&mut self, now: NaiveDateTime) {
+        if self.done.is_some() {
+            self.done = None;
+            return;
+        }
+        self.history.push(now);
+        match &self.recur {
+            Some(recur) => {
+                let today = now.date();
+                let next = match self.due {
+                    Some(due) => recur.roll(due.date, today),
+                    None => recur.next_after(today),
+                };
+                // a rule with no date left ends like a note without one
+                match next {
+                    Some(next) => {
+                        self.due = Some(Due {
+                            date: next,
+                            time: self.due.and_then(|due| due.time),
+                        })
+                    }
+                    None => self.done = Some(now),
+                }
+            }
+            None => self.done = Some(now),
+        }
+    }
 }
 
 fn main() -> Result<()> {
     color_eyre::install()?;
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(err) = cli::run(&args) {
+            eprintln!("Note: {}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let keymap = match Keymap::load(&file_path()) {
+        Ok(keymap) => keymap,
+        Err(errors) => {
+            for err in errors {
+                eprintln!("Note: {}", err);
+            }
+            std::process::exit(1);
+        }
+    };
+    input::use_vim_modes(keymap.vim);
+    match Theme::load(&file_path()) {
+        Ok(theme) => theme::set(theme),
+        Err(errors) => {
+            for err in errors {
+                eprintln!("Note: {}", err);
+            }
+            std::process::exit(1);
+        }
+    }
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let notes_raw: Vec<String> = load_notes()?
-        .into_iter()
-        .map(|line| line.trim_end().to_string())
-        .collect();
-    let mut line_cnt = notes_raw.len() as u32;
-    let mut notes: Vec<NoteFormat> = vec![];
-    // let mut items: Vec<ListItem> = notes_raw
-    // .iter()
-    // .map(|n| ListItem::new(n.as_str()))
-    // .collect();
-
-    let mut items: Vec<ListItem<'_>> = vec![];
-    let mut i = 0;
-    while i + 1 < notes_raw.len() {
-        let note = NoteFormat {
-            text: notes_raw[i].clone(),
-            body: notes_raw[i + 1].clone(),
-        };
-        items.push(ListItem::new(format!(
-            "{}: \"{}\" - \"{}\"",
-            ((i + 2) / 2),
-            note.text,
-            note.body
-        )));
-        notes.push(note);
-        i += 2;
+    let mut notes: Vec<NoteFormat> = load_note_list()?;
+    if auto_archive(&mut notes, Local::now().naive_local(), auto_archive_days()) > 0 {
+        save_meta(&notes)?;
     }
+    let mut line_cnt = notes.len() as u32;
+    let mut view_options = ViewOptions::default();
+    let mut collapsed: HashSet<String> = HashSet::new();
+    let mut tree_state = ListState::default();
+    tree_state.select(Some(0));
+    let mut tree_focus = false;
+    let mut move_popup_active = false;
+    let mut folder_input = TextInput::default();
+    let mut templates: Vec<Template> = vec![];
+    let mut template_state = ListState::default();
+    let mut delete_active = false;
+    let mut fuzzy_active = false;
+    let mut fuzzy_input = TextInput::default();
+    let mut fuzzy_state = ListState::default();
+    let mut search = Search::default();
+    let mut attach_popup_active: i8 = 0;
+    let mut attach_state = ListState::default();
+    let mut attach_input = TextInput::default();
+    let mut review: Option<Review> = None;
+    let mut label_popup_active = false;
+    let mut label_state = ListState::default();
+    let mut fields_popup_active = false;
+    let mut field_input = TextInput::default();
+    let mut filter_popup_active = false;
+    let mut filter_input = TextInput::default();
+    let mut filter_before: Option<Filter> = None;
+    let columns = fields::columns();
+    let mut link_picker_active = false;
+    let mut link_picker_state = ListState::default();
+    let mut jump_to: Option<usize> = None;
+    let mut back_stack: Vec<usize> = vec![];
+    let mut forward_stack: Vec<usize> = vec![];
+    let mut renamed: Option<(String, String)> = None;
 
     let mut action = false;
     let mut add_popup_active = 0;
     let mut edit_popup_active: i8 = 0;
     let mut edit_from_list_active: i8 = 0;
-    let mut edit_line_num: String = "".to_string();
+    let mut edit_line_num = TextInput::default();
+    let mut due_popup_active = false;
+    let mut due_input = TextInput::default();
+    let mut agenda_active = false;
+    let mut keys_active = false;
+    let mut recur_popup_active = false;
+    let mut recur_input = TextInput::default();
 
-    let mut note = NoteFormat::default();
+    let mut toasts = Toasts::default();
+    let mut undo_history = UndoHistory::default();
+    let mut pending = Pending::default();
+    let mut yanked: Option<NoteFormat> = None;
+    let mut title_input = TextInput::default();
+    let mut body_input = TextInput::default();
 
     let mut list_state = ListState::default();
     list_state.select(Some(0));
 
     loop {
         let mut key_event = None;
+        // note to keep selected if the list order changes under it
+        let mut follow: Option<usize> = None;
+
+        if let Some(target) = jump_to.take() {
+            let view = view::visible(&notes, &view_options);
+            if let Some(current) = list_state.selected().and_then(|i| view.get(i).copied()) {
+                back_stack.push(current);
+            }
+            forward_stack.clear();
+            show_note(&notes, target, &mut view_options, &mut tree_state);
+            follow = Some(target);
+        }
+
+        let view = view::visible(&notes, &view_options);
+        let selected_note = list_state.selected().and_then(|i| view.get(i).copied());
+        let tree_rows = folder_rows(&notes, view_options.panel, &collapsed);
+        let selected_folder = tree_state
+            .selected()
+            .and_then(|i| tree_rows.get(i))
+            .map(|row| row.path.clone());
 
         if event::poll(Duration::from_millis(50))? {
             if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('a') => {
-                        if !action {
-                            if add_popup_active == 0 {
-                                add_popup_active = 1;
-                            }
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Char('q') => {
-                        if !action {
-                            break;
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Char('F') => {
-                        if !action {
-                            filter_command();
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Char('f') => {
-                        if !action {
-                            find_command();
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Char('e') => {
-                        if !action {
-                            edit_popup_active = 1;
-                            //edit_command();
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Esc => {
-                        if !action {
-                            break;
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Down => {
-                        if !action {
-                            let i = list_state.selected().unwrap_or(0);
-                            let new_i = if i + 1 >= items.len() { 0 } else { i + 1 };
-                            list_state.select(Some(new_i));
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Up => {
-                        if !action {
-                            let i = list_state.selected().unwrap_or(0);
-                            let new_i = if i == 0 { items.len() - 1 } else { i - 1 };
-                            list_state.select(Some(new_i));
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Char('j') => {
-                        if !action {
-                            let i = list_state.selected().unwrap_or(0);
-                            let new_i = if i + 1 >= items.len() { 0 } else { i + 1 };
-                            list_state.select(Some(new_i));
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Char('k') => {
-                        if !action {
-                            let i = list_state.selected().unwrap_or(0);
-                            let new_i = if i == 0 { items.len() - 1 } else { i - 1 };
-                            list_state.select(Some(new_i));
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-                    KeyCode::Enter => {
-                        if !action {
-                            if let Some(index) = list_state.selected() {
-                                edit_line_num = (index + 1).to_string();
-                            }
-                            edit_from_list_active = 2; // NOTE: fn edit_text is used , so active set 2
-                        } else {
-                            key_event = Some(key);
-                        }
-                    }
-
-                    _ => {
-                        key_event = Some(key);
+                if action {
+                    key_event = Some(key);
+                } else {
+                    match keymap.feed(&mut pending, key) {
+                        Outcome::Pending => {}
+                        Outcome::Unbound => key_event = Some(key),
+                        Outcome::Action(act, count, counted) => match act {
+                            Action::Add => {
+                                if add_popup_active == 0 {
+                                    templates =
+                                        template::load_all(&template::templates_dir(&file_path()));
+                                    if templates.is_empty() {
+                                        add_popup_active = 1;
+                                    } else {
+                                        template_state.select(Some(0));
+                                        add_popup_active = 3; // NOTE: template picker first
+                                    }
+                                }
+                            }
+                            Action::Quit => {
+                                break;
+                            }
+                            Action::Filter => {
+                                filter_input = TextInput::new(
+                                    view_options
+                                        .filter
+                                        .as_ref()
+                                        .map(|filter| filter.to_string())
+                                        .unwrap_or_default(),
+                                );
+                                filter_before = view_options.filter.clone();
+                                filter_popup_active = true;
+                            }
+                            Action::Find => {
+                                search = Search {
+                                    scope: search.scope,
+                                    editing: true,
+                                    ..Default::default()
+                                };
+                            }
+                            Action::NextMatch | Action::PrevMatch => {
+                                if !search.query.is_empty() {
+                                    let forward = act == Action::NextMatch;
+                                    let matches =
+                                        view.iter().filter(|&&i| search.matches(&notes[i])).count();
+                                    // past the last match the steps go round again
+                                    let steps = if matches == 0 {
+                                        0
+                                    } else {
+                                        (count - 1) % matches + 1
+                                    };
+                                    for _ in 0..steps {
+                                        let from = list_state.selected().unwrap_or(0);
+                                        if let Some(pos) =
+                                            search.step(&notes, &view, from, forward, false)
+                                        {
+                                            list_state.select(Some(pos));
+                                        }
+                                    }
+                                }
+                            }
+                            Action::Escape => {
+                                if search.query.is_empty() {
+                                    break;
+                                }
+                                search = Search::default();
+                            }
+                            Action::Edit => {
+                                edit_popup_active = 1;
+                                //edit_command();
+                            }
+                            Action::Due => {
+                                if let Some(index) = selected_note {
+                                    due_input = TextInput::new(
+                                        notes[index]
+                                            .due
+                                            .map(|due| due.to_string())
+                                            .unwrap_or_default(),
+                                    );
+                                    due_popup_active = true;
+                                }
+                            }
+                            Action::Agenda => {
+                                agenda_active = true;
+                            }
+                            Action::Help => {
+                                keys_active = true;
+                            }
+                            Action::Repeat => {
+                                if let Some(index) = selected_note {
+                                    recur_input = TextInput::new(
+                                        notes[index]
+                                            .recur
+                                            .as_ref()
+                                            .map(|recur| recur.to_string())
+                                            .unwrap_or_default(),
+                                    );
+                                    recur_popup_active = true;
+                                }
+                            }
+                            Action::Done => {
+                                if let Some(index) = selected_note {
+                                    let result =
+                                        update_note(&mut notes, index, &mut undo_history, |note| {
+                                            note.toggle_done(Local::now().naive_local())
+                                        });
+                                    toasts.report(result, "Could not save");
+                                    follow = Some(index);
+                                }
+                            }
+                            Action::Priority => {
+                                if let Some(index) = selected_note {
+                                    let result =
+                                        update_note(&mut notes, index, &mut undo_history, |note| {
+                                            note.priority = note.priority.next()
+                                        });
+                                    toasts.report(result, "Could not save");
+                                    follow = Some(index);
+                                }
+                            }
+                            Action::Pin => {
+                                if let Some(index) = selected_note {
+                                    let result =
+                                        update_note(&mut notes, index, &mut undo_history, |note| {
+                                            note.pinned = !note.pinned
+                                        });
+                                    toasts.report(result, "Could not save");
+                                    follow = Some(index);
+                                }
+                            }
+                            Action::Archive => {
+                                if let Some(index) = selected_note {
+                                    let result =
+                                        update_note(&mut notes, index, &mut undo_history, |note| {
+                                            note.archived = !note.archived
+                                        });
+                                    toasts.report(result, "Could not save");
+                                }
+                            }
+                            Action::ShowNotes => {
+                                view_options.panel = Panel::Notes;
+                                list_state.select(Some(0));
+                            }
+                            Action::ShowArchive => {
+                                view_options.panel = Panel::Archive;
+                                list_state.select(Some(0));
+                            }
+                            Action::Sort => {
+                                view_options.sort = view_options.sort.next();
+                                follow = selected_note;
+                            }
+                            Action::OpenLink => {
+                                if let Some(index) = selected_note {
+                                    let targets = links::targets(&notes[index].body);
+                                    match targets.as_slice() {
+                                        [] => toasts.info("No [[links]] in this note"),
+                                        [target] => {
+                                            jump_to = links::resolve(target, &notes);
+                                            if jump_to.is_none() {
+                                                toasts.error(format!(
+                                                    "No note matches [[{}]]",
+                                                    target
+                                                ));
+                                            }
+                                        }
+                                        _ => {
+                                            link_picker_state.select(Some(0));
+                                            link_picker_active = true;
+                                        }
+                                    }
+                                }
+                            }
+                            Action::Back => {
+                                if let Some(target) = back_stack.pop() {
+                                    if let Some(current) = selected_note {
+                                        forward_stack.push(current);
+                                    }
+                                    show_note(&notes, target, &mut view_options, &mut tree_state);
+                                    follow = Some(target);
+                                }
+                            }
+                            Action::Forward => {
+                                if let Some(target) = forward_stack.pop() {
+                                    if let Some(current) = selected_note {
+                                        back_stack.push(current);
+                                    }
+                                    show_note(&notes, target, &mut view_options, &mut tree_state);
+                                    follow = Some(target);
+                                }
+                            }
+                            Action::Folders => {
+                                tree_focus = !tree_focus;
+                            }
+                            Action::Today => {
+                                let format = journal::date_format();
+                                let now = Local::now().naive_local();
+                                jump_to =
+                                    journal::find(&notes, now.date(), &format).or_else(|| {
+                                        let templates = template::load_all(
+                                            &template::templates_dir(&file_path()),
+                                        );
+                                        let (text, body) = journal::new_entry(
+                                            now.date(),
+                                            now,
+                                            &format,
+                                            &templates,
+                                            None,
+                                        );
+                                        let note = NoteFormat {
+                                            text,
+                                            body,
+                                            ..Default::default()
+                                        };
+                                        toasts.report(
+                                            add_note(&mut notes, note),
+                                            "Could not add the journal note",
+                                        )?;
+                                        line_cnt = notes.len() as u32;
+                                        undo_history.record(Change::Added {
+                                            index: notes.len() - 1,
+                                            note: notes[notes.len() - 1].clone(),
+                                        });
+                                        Some(notes.len() - 1)
+                                    });
+                            }
+                            Action::PrevDay | Action::NextDay => {
+                                let format = journal::date_format();
+                                let date = selected_note
+                                    .and_then(|index| journal::date_of(&notes[index], &format))
+                                    .unwrap_or_else(|| Local::now().date_naive());
+                                let forward = act == Action::NextDay;
+                                if let Some(index) = journal::step(&notes, date, forward, &format) {
+                                    jump_to = Some(index);
+                                }
+                            }
+                            Action::Timer => {
+                                if let Some(index) = selected_note {
+                                    let now = Local::now().naive_local();
+                                    let result =
+                                        update_notes(&mut notes, &mut undo_history, |notes| {
+                                            // only one timer runs at a time
+                                            for (i, note) in notes.iter_mut().enumerate() {
+                                                if i != index && note.timer.is_some() {
+                                                    note.toggle_timer(now);
+                                                }
+                                            }
+                                            notes[index].toggle_timer(now);
+                                        });
+                                    toasts.report(result, "Could not save");
+                                }
+                            }
+                            Action::Undo | Action::Redo => {
+                                follow = undo_change(
+                                    &mut notes,
+                                    &mut undo_history,
+                                    act == Action::Redo,
+                                    count,
+                                    [&mut back_stack, &mut forward_stack],
+                                    &mut toasts,
+                                );
+                                line_cnt = notes.len() as u32;
+                            }
+                            Action::Review => {
+                                let queue =
+                                    review::due_notes(&notes, &view, Local::now().date_naive());
+                                if queue.is_empty() {
+                                    toasts.info("No notes are due for review");
+                                } else {
+                                    review = Some(Review {
+                                        queue,
+                                        pos: 0,
+                                        revealed: false,
+                                    });
+                                }
+                            }
+                            Action::Attachments => {
+                                if selected_note.is_some() {
+                                    attach_state.select(Some(0));
+                                    attach_popup_active = 1;
+                                }
+                            }
+                            Action::Delete => {
+                                if selected_note.is_some() {
+                                    delete_active = true;
+                                }
+                            }
+                            Action::DeleteNow => {
+                                if let Some(index) = selected_note {
+                                    let result = delete_note(
+                                        &mut notes,
+                                        index,
+                                        [&mut back_stack, &mut forward_stack],
+                                        &mut line_cnt,
+                                        &mut undo_history,
+                                        &mut toasts,
+                                    );
+                                    toasts.report(result, "Could not save");
+                                }
+                            }
+                            Action::Yank => {
+                                if let Some(index) = selected_note {
+                                    yanked = Some(notes[index].clone());
+                                    toasts.info(format!("Yanked note {}", index + 1));
+                                }
+                            }
+                            Action::Paste => {
+                                if let Some(note) = &yanked {
+                                    // a copy starts afresh: not done, reviewed or timed
+                                    let note = NoteFormat {
+                                        text: note.text.clone(),
+                                        body: note.body.clone(),
+                                        due: note.due,
+                                        priority: note.priority,
+                                        folder: note.folder.clone(),
+                                        recur: note.recur.clone(),
+                                        fields: note.fields.clone(),
+                                        label: note.label,
+                                        attachments: note.attachments.clone(),
+                                        ..Default::default()
+                                    };
+                                    if toasts
+                                        .report(add_note(&mut notes, note), "Could not save")
+                                        .is_some()
+                                    {
+                                        let index = notes.len() - 1;
+                                        line_cnt = notes.len() as u32;
+                                        undo_history.record(Change::Added {
+                                            index,
+                                            note: notes[index].clone(),
+                                        });
+                                        follow = Some(index);
+                                        toasts.info(format!("Pasted as note {}", index + 1));
+                                    }
+                                }
+                            }
+                            Action::Jump => {
+                                fuzzy_state.select(Some(0));
+                                fuzzy_active = true;
+                            }
+                            Action::Label => {
+                                if let Some(index) = selected_note {
+                                    let current = notes[index]
+                                        .label
+                                        .and_then(|label| {
+                                            Label::ALL.iter().position(|&l| l == label)
+                                        })
+                                        .map_or(0, |i| i + 1);
+                                    label_state.select(Some(current));
+                                    label_popup_active = true;
+                                }
+                            }
+                            Action::Fields => {
+                                if selected_note.is_some() {
+                                    fields_popup_active = true;
+                                }
+                            }
+                            Action::Move => {
+                                if let Some(index) = selected_note {
+                                    folder_input = TextInput::new(notes[index].folder.clone());
+                                    move_popup_active = true;
+                                }
+                            }
+                            Action::Collapse | Action::Expand | Action::ToggleFolder => {
+                                if let Some(folder) =
+                                    selected_folder.filter(|f| tree_focus && !f.is_empty())
+                                {
+                                    let collapse = match act {
+                                        Action::Collapse => true,
+                                        Action::Expand => false,
+                                        _ => !collapsed.contains(&folder),
+                                    };
+                                    if collapse {
+                                        collapsed.insert(folder);
+                                    } else {
+                                        collapsed.remove(&folder);
+                                    }
+                                }
+                            }
+                            Action::Down | Action::Up => {
+                                let (state, len) = if tree_focus {
+                                    (&mut tree_state, tree_rows.len())
+                                } else {
+                                    (&mut list_state, view.len())
+                                };
+                                if len > 0 {
+                                    // wrapping around the ends, as single steps do
+                                    let i = state.selected().unwrap_or(0) % len;
+                                    let new_i = if act == Action::Down {
+                                        (i + count % len) % len
+                                    } else {
+                                        (i + len - count % len) % len
+                                    };
+                                    state.select(Some(new_i));
+                                }
+                            }
+                            Action::Top | Action::Bottom => {
+                                let (state, len) = if tree_focus {
+                                    (&mut tree_state, tree_rows.len())
+                                } else {
+                                    (&mut list_state, view.len())
+                                };
+                                let last = len.saturating_sub(1);
+                                let new_i = match act {
+                                    _ if counted => (count - 1).min(last),
+                                    Action::Top => 0,
+                                    _ => last,
+                                };
+                                state.select(Some(new_i));
+                            }
+                            Action::HalfPageDown | Action::HalfPageUp => {
+                                let (state, len) = if tree_focus {
+                                    (&mut tree_state, tree_rows.len())
+                                } else {
+                                    (&mut list_state, view.len())
+                                };
+                                // the list is the screen less the header and footer
+                                let page = (terminal.size()?.height.saturating_sub(11) / 2).max(1)
+                                    as usize
+                                    * count;
+                                let i = state.selected().unwrap_or(0);
+                                let new_i = if act == Action::HalfPageDown {
+                                    (i + page).min(len.saturating_sub(1))
+                                } else {
+                                    i.saturating_sub(page)
+                                };
+                                state.select(Some(new_i));
+                            }
+                            Action::EditSelected => {
+                                if let Some(index) = selected_note {
+                                    start_edit(
+                                        &notes,
+                                        index,
+                                        &mut edit_line_num,
+                                        &mut title_input,
+                                        &mut body_input,
+                                    );
+                                    edit_from_list_active = 2; // NOTE: fn edit_text is used , so active set 2
+                                }
+                            }
+                        },
                     }
                 }
             }
         }
 
+        let tree_rows = folder_rows(&notes, view_options.panel, &collapsed);
+        if tree_state.selected().is_some_and(|i| i >= tree_rows.len()) {
+            tree_state.select(Some(tree_rows.len() - 1));
+        }
+        let folder = tree_state
+            .selected()
+            .and_then(|i| tree_rows.get(i))
+            .map(|row| row.path.clone())
+            .filter(|path| !path.is_empty());
+        if folder != view_options.folder {
+            view_options.folder = folder;
+            list_state.select(Some(0));
+        }
+        let tree_items: Vec<ListItem> = tree_rows
+            .iter()
+            .map(|row| folder_tree_item(row, &collapsed))
+            .collect();
+
+        let view = view::visible(&notes, &view_options);
+        if let Some(pos) = follow.and_then(|n| view.iter().position(|&i| i == n)) {
+            list_state.select(Some(pos));
+        }
+        if list_state.selected().is_some_and(|i| i >= view.len()) {
+            list_state.select(Some(view.len().saturating_sub(1)));
+        }
+        let selected_note = list_state.selected().and_then(|i| view.get(i).copied());
+        let column_widths: Vec<(&str, usize)> = columns
+            .iter()
+            .map(|key| {
+                let width = view
+                    .iter()
+                    .filter_map(|&i| notes[i].fields.get(key))
+                    .map(|value| value.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                (key.as_str(), width)
+            })
+            .filter(|(_, width)| *width > 0)
+            .collect();
+        let items: Vec<ListItem> = view
+            .iter()
+            .map(|&i| note_list_item(i, &notes[i], &column_widths, &search))
+            .collect();
+        let attachment_items: Vec<ListItem> = selected_note
+            .map(|index| {
+                notes[index]
+                    .attachments
+                    .iter()
+                    .map(|path| attachment_item(path))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let now = Local::now().naive_local();
+        let status = notes
+            .iter()
+            .find_map(|note| {
+                let start = note.timer?;
+                Some(format!(
+                    " ⏱ {} {} ",
+                    timelog::format_clock(now - start),
+                    note.text.trim()
+                ))
+            })
+            .unwrap_or_default();
+        let backlink_items: Vec<ListItem> = selected_note
+            .map(|index| links::backlinks(&notes, index))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|i| ListItem::new(format!("{}: {}", i + 1, notes[i].text)))
+            .collect();
+
         terminal.draw(|f| {
-            draw_main_ui(f, &items, &mut list_state);
+            draw_main_ui(
+                f,
+                &items,
+                &mut list_state,
+                &tree_items,
+                &mut tree_state,
+                tree_focus,
+                &view_options,
+                &backlink_items,
+                &attachment_items,
+                &status,
+                &keymap,
+                &pending.to_string(),
+            );
 
             let current_key = key_event.unwrap_or_else(|| {
                 if add_popup_active != 0 {
@@ -696,43 +3032,239 @@ fn main() -> Result<()> {
                 }
             });
 
+            if search.editing || !search.query.is_empty() {
+                find_command(
+                    f,
+                    &mut search,
+                    &notes,
+                    &view,
+                    &mut list_state,
+                    current_key,
+                    &mut action,
+                );
+            }
             if add_popup_active != 0 {
-                let _ = add_command(
+                let result = add_command(
                     f,
                     &mut add_popup_active,
                     &mut notes,
-                    &mut items,
-                    &mut note,
+                    &mut title_input,
+                    &mut body_input,
                     current_key,
                     &mut action,
                     &mut line_cnt,
+                    &templates,
+                    &mut template_state,
+                    &mut undo_history,
+                    &mut toasts,
                 );
+                toasts.report(result, "Could not add the note");
             }
             if edit_popup_active != 0 {
-                let _ = edit_command(
+                let result = edit_command(
                     f,
                     &mut edit_popup_active,
                     &mut notes,
-                    &mut items,
-                    &mut note,
+                    &mut title_input,
+                    &mut body_input,
                     current_key,
                     &mut action,
                     line_cnt,
                     &mut edit_line_num,
+                    &mut renamed,
+                    &mut undo_history,
+                    &mut toasts,
                 );
+                toasts.report(result, "Could not save the note");
             }
             if edit_from_list_active != 0 {
-                let _ = edit_from_list(
+                let result = edit_from_list(
                     f,
                     &mut edit_from_list_active,
                     &mut notes,
-                    &mut items,
-                    &mut note,
+                    &mut title_input,
+                    &mut body_input,
                     current_key,
                     &mut action,
                     line_cnt,
                     &mut edit_line_num,
+                    &mut renamed,
+                    &mut undo_history,
+                    &mut toasts,
+                );
+                toasts.report(result, "Could not save the note");
+            }
+            if due_popup_active {
+                let result = due_date_input(
+                    f,
+                    &mut due_popup_active,
+                    &mut notes,
+                    &mut due_input,
+                    selected_note.unwrap_or(usize::MAX),
+                    current_key,
+                    &mut action,
+                    &mut undo_history,
+                    &mut toasts,
+                );
+                toasts.report(result, "Could not save");
+            }
+            if recur_popup_active {
+                let result = recurrence_input(
+                    f,
+                    &mut recur_popup_active,
+                    &mut notes,
+                    &mut recur_input,
+                    selected_note.unwrap_or(usize::MAX),
+                    current_key,
+                    &mut action,
+                    &mut undo_history,
+                    &mut toasts,
+                );
+                toasts.report(result, "Could not save");
+            }
+            if move_popup_active {
+                let result = move_input(
+                    f,
+                    &mut move_popup_active,
+                    &mut notes,
+                    &mut folder_input,
+                    selected_note.unwrap_or(usize::MAX),
+                    current_key,
+                    &mut action,
+                    &mut undo_history,
+                );
+                toasts.report(result, "Could not save");
+            }
+            if agenda_active {
+                agenda_view(f, &mut agenda_active, &notes, current_key, &mut action);
+            }
+            if keys_active {
+                keys_view(f, &mut keys_active, &keymap, current_key, &mut action);
+            }
+            if delete_active {
+                let result = delete_confirm(
+                    f,
+                    &mut delete_active,
+                    &mut notes,
+                    selected_note.unwrap_or(usize::MAX),
+                    [&mut back_stack, &mut forward_stack],
+                    &mut line_cnt,
+                    current_key,
+                    &mut action,
+                    &mut undo_history,
+                    &mut toasts,
+                );
+                toasts.report(result, "Could not delete the note");
+            }
+            if fuzzy_active {
+                fuzzy_finder(
+                    f,
+                    &mut fuzzy_active,
+                    &notes,
+                    &view,
+                    &mut fuzzy_input,
+                    &mut fuzzy_state,
+                    &mut jump_to,
+                    &mut edit_line_num,
+                    &mut title_input,
+                    &mut body_input,
+                    &mut edit_from_list_active,
+                    current_key,
+                    &mut action,
+                );
+            }
+            if attach_popup_active != 0 {
+                let result = attachments_view(
+                    f,
+                    &mut attach_popup_active,
+                    &mut notes,
+                    selected_note.unwrap_or(usize::MAX),
+                    &mut attach_state,
+                    &mut attach_input,
+                    current_key,
+                    &mut action,
+                    &mut undo_history,
+                    &mut toasts,
+                );
+                toasts.report(result, "Could not save");
+            }
+            if review.is_some() {
+                let result = review_view(
+                    f,
+                    &mut review,
+                    &mut notes,
+                    current_key,
+                    &mut action,
+                    &mut undo_history,
+                    &mut toasts,
+                );
+                toasts.report(result, "Could not save");
+            }
+            if label_popup_active {
+                let result = label_picker(
+                    f,
+                    &mut label_popup_active,
+                    &mut notes,
+                    selected_note.unwrap_or(usize::MAX),
+                    &mut label_state,
+                    current_key,
+                    &mut action,
+                    &mut undo_history,
                 );
+                toasts.report(result, "Could not save");
+            }
+            if fields_popup_active {
+                let result = fields_input(
+                    f,
+                    &mut fields_popup_active,
+                    &mut notes,
+                    &mut field_input,
+                    selected_note.unwrap_or(usize::MAX),
+                    current_key,
+                    &mut action,
+                    &mut undo_history,
+                    &mut toasts,
+                );
+                toasts.report(result, "Could not save");
+            }
+            if filter_popup_active {
+                filter_command(
+                    f,
+                    &mut filter_popup_active,
+                    &mut view_options,
+                    &mut filter_input,
+                    &mut filter_before,
+                    current_key,
+                    &mut action,
+                );
+            }
+            if link_picker_active {
+                link_picker(
+                    f,
+                    &mut link_picker_active,
+                    &notes,
+                    selected_note.unwrap_or(usize::MAX),
+                    &mut link_picker_state,
+                    &mut jump_to,
+                    current_key,
+                    &mut action,
+                    &mut toasts,
+                );
+            }
+            if renamed.is_some() && edit_popup_active == 0 && edit_from_list_active == 0 {
+                let result = rename_links_prompt(
+                    f,
+                    &mut renamed,
+                    &mut notes,
+                    current_key,
+                    &mut action,
+                    &mut undo_history,
+                    &mut toasts,
+                );
+                toasts.report(result, "Could not save");
+            }
+            if let Some(toast) = toasts.current(Instant::now()) {
+                draw_toast(f, toast);
             }
         })?;
     }
@@ -743,3 +3275,65 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meta_lines_read_back() {
+        let at = |s| meta::parse_datetime(s).unwrap();
+        let due = Due::parse("2024-05-16 09:30").unwrap();
+        let mut note = NoteFormat {
+            text: "title".to_string(),
+            body: "body".to_string(),
+            due: Some(due),
+            priority: Priority::High,
+            created: Some(at("2024-05-01T08:00")),
+            pinned: true,
+            archived: true,
+            folder: "work/q3 plans".to_string(),
+            done: Some(at("2024-05-15T10:00")),
+            recur: Recurrence::parse("weekly", due.date),
+            history: vec![at("2024-05-08T10:00"), at("2024-05-15T10:00")],
+            label: Some(Label::Green),
+            attachments: vec![
+                "/home/me/a file=1.txt".to_string(),
+                r"C:\Users\me\100% done.pdf".to_string(),
+            ],
+            card: Card::parse("2.36,6,2,2024-05-21"),
+            timer: Some(at("2024-05-15T11:00")),
+            sessions: timelog::parse_datetime("2024-05-14T09:00")
+                .zip(timelog::parse_datetime("2024-05-14T09:45"))
+                .map(|(start, end)| Session { start, end })
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        note.fields.set("ticket", "OPS-123");
+        note.fields.set("query", "a=b c==d");
+        note.fields.set("path", r"C:\tmp\x y\");
+        note.fields.set("lines", "one\ntwo\r\nthree\t");
+        note.fields.set("odd%key", "%41 is not A");
+
+        let line = note.meta_line();
+        assert!(!line.contains('\n'));
+        let mut read = NoteFormat {
+            text: note.text.clone(),
+            body: note.body.clone(),
+            ..Default::default()
+        };
+        read.apply_meta(&line);
+        assert_eq!(read, note);
+        assert_eq!(read.meta_line(), line);
+    }
+
+    #[test]
+    fn empty_meta_lines_leave_the_defaults() {
+        let note = NoteFormat::default();
+        assert_eq!(note.meta_line(), "");
+        let mut read = NoteFormat::default();
+        read.apply_meta("");
+        assert_eq!(read, note);
+    }
+}
@@ -0,0 +1,137 @@
+//! Sidecar file holding everything about a note except its title and body.
+//!
+//! `note.txt` keeps the fixed-width title/body pairs the zsh script reads and
+//! rewrites in place, so extra fields are stored in `note.meta` next to it.
+//! Line `n` of the sidecar belongs to note `n`; a missing or empty line means
+//! the note has no extra fields. Each line is a space separated list of
+//! `key=value` pairs with `%`, `=`, spaces and control characters escaped.
+
+use chrono::NaiveDateTime;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+pub fn meta_path(notes_path: &str) -> String {
+    match notes_path.strip_suffix(".txt") {
+        Some(stem) => format!("{}.meta", stem),
+        None => format!("{}.meta", notes_path),
+    }
+}
+
+pub fn load_lines(path: &str) -> io::Result<Vec<String>> {
+    if !Path::new(path).exists() {
+        return Ok(vec![]);
+    }
+    let reader = BufReader::new(File::open(path)?);
+    Ok(reader.lines().map_while(Result::ok).collect())
+}
+
+pub fn save_lines(path: &str, lines: &[String]) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+pub fn parse_line(line: &str) -> Vec<(String, String)> {
+    line.split(' ')
+        .filter(|pair| !pair.is_empty())
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            Some((unescape(key), unescape(value)))
+        })
+        .collect()
+}
+
+pub fn format_line(pairs: &[(&str, String)]) -> String {
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", escape(key), escape(value)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+pub fn format_datetime(dt: NaiveDateTime) -> String {
+    dt.format(DATETIME_FORMAT).to_string()
+}
+
+pub fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, DATETIME_FORMAT).ok()
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '%' | '=' | ' ' => out.push_str(&format!("%{:02X}", c as u32)),
+            c if c.is_control() && (c as u32) < 0x80 => out.push_str(&format!("%{:02X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let hex: String = chars.by_ref().take(2).collect();
+        match u8::from_str_radix(&hex, 16) {
+            Ok(b) => out.push(b as char),
+            Err(_) => {
+                out.push('%');
+                out.push_str(&hex);
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_survive_escaping() {
+        let pairs = [
+            ("plain", "value".to_string()),
+            ("equals", "a=b==c".to_string()),
+            ("spaces", " two  words ".to_string()),
+            ("backslashes", r"C:\notes\a b\".to_string()),
+            ("newlines", "line one\nline two\r\n\ttabbed".to_string()),
+            ("percent", "100% %41 %zz %".to_string()),
+            ("unicode", "café → 日本".to_string()),
+            ("empty", String::new()),
+            ("odd key=% ", "x".to_string()),
+        ];
+        let line = format_line(&pairs);
+        assert!(!line.contains('\n') && !line.contains('\t'));
+        assert_eq!(line.split(' ').count(), pairs.len());
+        let parsed = parse_line(&line);
+        let expected: Vec<(String, String)> = pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn escapes_are_percent_hex() {
+        assert_eq!(
+            format_line(&[("k", "a=b c%\n\\".to_string())]),
+            r"k=a%3Db%20c%25%0A\"
+        );
+        // not an escape, so kept as written
+        assert_eq!(
+            parse_line("k=50%zz"),
+            [("k".to_string(), "50%zz".to_string())]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Recurrence rules for notes with due dates.
+//!
+//! Rules are written the way they are displayed: `daily`, `every 3 days`,
+//! `weekly`, `weekly mon,thu`, `monthly` and `monthly 15`. `毎日`, `毎週月曜`
+//! and `毎月15日` are accepted as well.
+
+use chrono::{Datelike, Duration, Months, NaiveDate, Weekday};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Recurrence {
+    EveryDays(u32),
+    /// On the given weekdays, in Monday-first order.
+    Weekly(Vec<Weekday>),
+    /// On the given day of the month, or the last day of shorter months.
+    Monthly(u32),
+}
+
+impl Recurrence {
+    /// `anchor` fills in the weekday or day of month when the rule leaves it
+    /// out, so `weekly` on a Thursday due date means every Thursday.
+    pub fn parse(s: &str, anchor: NaiveDate) -> Option<Recurrence> {
+        let s = s
+            .trim()
+            .to_lowercase()
+            .replace("毎日", "daily")
+            .replace("毎週", "weekly ")
+            .replace("毎月", "monthly ");
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let rule = match words.as_slice() {
+            ["daily"] | ["every", "day"] => Recurrence::EveryDays(1),
+            ["every", n, "day" | "days"] => Recurrence::EveryDays(n.parse().ok()?),
+            ["every", n, "week" | "weeks"] => {
+                Recurrence::EveryDays(n.parse::<u32>().ok()?.checked_mul(7)?)
+            }
+            ["every", "week"] | ["weekly"] => Recurrence::Weekly(vec![anchor.weekday()]),
+            ["every", "month"] | ["monthly"] => Recurrence::Monthly(anchor.day()),
+            ["weekly" | "every", days] => {
+                let mut days = days.split(',').map(weekday).collect::<Option<Vec<_>>>()?;
+                days.sort_by_key(|d| d.num_days_from_monday());
+                days.dedup();
+                Recurrence::Weekly(days)
+            }
+            ["monthly", day] | ["monthly", "on", day] | ["monthly", "on", "the", day] => {
+                let day = day.trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '日');
+                Recurrence::Monthly(day.parse().ok()?)
+            }
+            _ => return None,
+        };
+        match rule {
+            Recurrence::EveryDays(0) => None,
+            Recurrence::Monthly(day) if !(1..=31).contains(&day) => None,
+            rule => Some(rule),
+        }
+    }
+
+    /// The first occurrence strictly after `date`, or `None` when it is
+    /// past the last date chrono can represent.
+    pub fn next_after(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Recurrence::EveryDays(n) => date.checked_add_signed(Duration::try_days(*n as i64)?),
+            Recurrence::Weekly(days) => (1..=7)
+                .map_while(|i| date.checked_add_signed(Duration::try_days(i)?))
+                .find(|d| days.contains(&d.weekday())),
+            Recurrence::Monthly(day) => {
+                let this_month = clamp_day(date, *day);
+                if this_month > date {
+                    Some(this_month)
+                } else {
+                    let next_month = date.with_day(1)?.checked_add_months(Months::new(1))?;
+                    Some(clamp_day(next_month, *day))
+                }
+            }
+        }
+    }
+
+    /// Next due date after completing an occurrence due on `due`. Occurrences
+    /// that are already in the past are skipped, so finishing a task that
+    /// was missed for two weeks doesn't leave it overdue.
+    pub fn roll(&self, due: NaiveDate, today: NaiveDate) -> Option<NaiveDate> {
+        let mut next = self.next_after(due)?;
+        while next < today {
+            next = self.next_after(next)?;
+        }
+        Some(next)
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recurrence::EveryDays(1) => write!(f, "daily"),
+            Recurrence::EveryDays(n) => write!(f, "every {} days", n),
+            Recurrence::Weekly(days) => {
+                let days: Vec<String> = days.iter().map(|d| d.to_string().to_lowercase()).collect();
+                write!(f, "weekly {}", days.join(","))
+            }
+            Recurrence::Monthly(day) => write!(f, "monthly {}", day),
+        }
+    }
+}
+
+/// `day` in the month of `month`, or the last day of the month if it is shorter.
+fn clamp_day(month: NaiveDate, day: u32) -> NaiveDate {
+    (28..=day)
+        .rev()
+        .find_map(|d| month.with_day(d))
+        .or_else(|| month.with_day(day))
+        .unwrap_or(month)
+}
+
+fn weekday(s: &str) -> Option<Weekday> {
+    let s = s.trim();
+    let s = s
+        .strip_suffix("曜日")
+        .or_else(|| s.strip_suffix('曜'))
+        .unwrap_or(s);
+    match s {
+        "mon" | "monday" | "月" => Some(Weekday::Mon),
+        "tue" | "tuesday" | "火" => Some(Weekday::Tue),
+        "wed" | "wednesday" | "水" => Some(Weekday::Wed),
+        "thu" | "thursday" | "木" => Some(Weekday::Thu),
+        "fri" | "friday" | "金" => Some(Weekday::Fri),
+        "sat" | "saturday" | "土" => Some(Weekday::Sat),
+        "sun" | "sunday" | "日" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn intervals_that_overflow_are_rejected() {
+        let anchor = date(2026, 10, 21);
+        assert_eq!(Recurrence::parse("every 999999999 weeks", anchor), None);
+        assert_eq!(
+            Recurrence::parse("every 3 weeks", anchor),
+            Some(Recurrence::EveryDays(21))
+        );
+    }
+
+    #[test]
+    fn no_next_date_past_the_end_of_the_calendar() {
+        let today = date(2026, 10, 21);
+        let rule = Recurrence::parse("every 99999999 days", today).unwrap();
+        assert_eq!(rule.next_after(today), None);
+        assert_eq!(rule.roll(today, today), None);
+        assert_eq!(
+            Recurrence::Weekly(vec![Weekday::Mon]).next_after(NaiveDate::MAX),
+            None
+        );
+        assert_eq!(Recurrence::Monthly(15).next_after(NaiveDate::MAX), None);
+    }
+
+    #[test]
+    fn roll_skips_missed_occurrences() {
+        let rule = Recurrence::parse("weekly mon,thu", date(2026, 10, 19)).unwrap();
+        assert_eq!(
+            rule.roll(date(2026, 10, 1), date(2026, 10, 21)),
+            Some(date(2026, 10, 22))
+        );
+        assert_eq!(
+            Recurrence::Monthly(31).next_after(date(2026, 1, 31)),
+            Some(date(2026, 2, 28))
+        );
+    }
+}
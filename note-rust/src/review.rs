@@ -0,0 +1,204 @@
+//! Spaced-repetition review: title as the question, body as the answer.
+//!
+//! Scheduling follows SM-2. Each review grades recall from 0 (forgotten)
+//! to 5 (perfect); grades below 3 start the card over, higher ones grow
+//! the interval by the card's ease factor, which itself drifts with the
+//! grades.
+
+use chrono::{Duration, NaiveDate};
+
+use crate::NoteFormat;
+
+/// Scheduling state of a note that has been reviewed at least once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Card {
+    pub ease: f64,
+    /// Days until the next review.
+    pub interval: u32,
+    /// Successful reviews in a row.
+    pub reps: u32,
+    pub due: NaiveDate,
+}
+
+impl Card {
+    /// `ease,interval,reps,due`, as stored in `note.meta`.
+    pub fn to_meta(self) -> String {
+        format!(
+            "{:.2},{},{},{}",
+            self.ease, self.interval, self.reps, self.due
+        )
+    }
+
+    pub fn parse(s: &str) -> Option<Card> {
+        let mut parts = s.split(',');
+        let card = Card {
+            ease: parts.next()?.parse().ok()?,
+            interval: parts.next()?.parse().ok()?,
+            reps: parts.next()?.parse().ok()?,
+            due: NaiveDate::parse_from_str(parts.next()?, "%Y-%m-%d").ok()?,
+        };
+        parts.next().is_none().then_some(card)
+    }
+}
+
+/// The grades offered in review mode, as (key, name, SM-2 grade).
+pub const GRADES: [(char, &str, u8); 4] = [
+    ('1', "again", 1),
+    ('2', "hard", 3),
+    ('3', "good", 4),
+    ('4', "easy", 5),
+];
+
+/// The card after a review graded `grade` (0-5) on `today`. `None` is a
+/// note that was never reviewed.
+pub fn schedule(card: Option<Card>, grade: u8, today: NaiveDate) -> Card {
+    let card = card.unwrap_or(Card {
+        ease: 2.5,
+        interval: 0,
+        reps: 0,
+        due: today,
+    });
+    let q = grade.min(5) as f64;
+    let ease = (card.ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(1.3);
+    let (reps, interval) = if grade < 3 {
+        (0, 1)
+    } else {
+        let interval = match card.reps {
+            0 => 1,
+            1 => 6,
+            _ => (card.interval as f64 * ease).round() as u32,
+        };
+        (card.reps + 1, interval)
+    };
+    Card {
+        ease,
+        interval,
+        reps,
+        due: today + Duration::days(interval as i64),
+    }
+}
+
+/// Notes among `candidates` that are due for review on `today`, the ones
+/// never reviewed last.
+pub fn due_notes(notes: &[NoteFormat], candidates: &[usize], today: NaiveDate) -> Vec<usize> {
+    let mut due: Vec<usize> = candidates
+        .iter()
+        .copied()
+        .filter(|&i| notes[i].card.is_none_or(|card| card.due <= today))
+        .collect();
+    due.sort_by_key(|&i| (notes[i].card.is_none(), notes[i].card.map(|card| card.due)));
+    due
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn card(ease: f64, interval: u32, reps: u32, due: NaiveDate) -> Card {
+        Card {
+            ease,
+            interval,
+            reps,
+            due,
+        }
+    }
+
+    fn assert_card(actual: Card, expected: Card, grade: u8) {
+        assert!(
+            (actual.ease - expected.ease).abs() < 1e-9
+                && (actual.interval, actual.reps, actual.due)
+                    == (expected.interval, expected.reps, expected.due),
+            "grade {}: {:?}, expected {:?}",
+            grade,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn grades_update_ease_and_interval() {
+        let today = date(2026, 10, 21);
+        let reviewed = card(2.5, 10, 2, today);
+        // grade, ease, interval, reps
+        for (grade, ease, interval, reps) in [
+            (0, 1.7, 1, 0),
+            (1, 1.96, 1, 0),
+            (2, 2.18, 1, 0),
+            (3, 2.36, 24, 3),
+            (4, 2.5, 25, 3),
+            (5, 2.6, 26, 3),
+        ] {
+            let due = today + Duration::days(interval as i64);
+            assert_card(
+                schedule(Some(reviewed), grade, today),
+                card(ease, interval, reps, due),
+                grade,
+            );
+        }
+        // grades above 5 count as 5
+        assert_card(
+            schedule(Some(reviewed), 9, today),
+            card(2.6, 26, 3, date(2026, 11, 16)),
+            9,
+        );
+    }
+
+    #[test]
+    fn first_reviews_take_one_then_six_days() {
+        let today = date(2026, 10, 21);
+        let first = schedule(None, 4, today);
+        assert_card(first, card(2.5, 1, 1, date(2026, 10, 22)), 4);
+        let second = schedule(Some(first), 4, first.due);
+        assert_card(second, card(2.5, 6, 2, date(2026, 10, 28)), 4);
+        let third = schedule(Some(second), 4, second.due);
+        assert_card(third, card(2.5, 15, 3, date(2026, 11, 12)), 4);
+    }
+
+    #[test]
+    fn ease_stops_at_the_minimum() {
+        let today = date(2026, 10, 21);
+        let hard = card(1.4, 10, 4, today);
+        assert_card(
+            schedule(Some(hard), 0, today),
+            card(1.3, 1, 0, date(2026, 10, 22)),
+            0,
+        );
+        assert_card(
+            schedule(Some(hard), 3, today),
+            card(1.3, 13, 5, date(2026, 11, 3)),
+            3,
+        );
+    }
+
+    #[test]
+    fn due_notes_come_earliest_first_then_new_ones() {
+        let today = date(2026, 10, 21);
+        let with_card = |due| NoteFormat {
+            card: Some(card(2.5, 1, 1, due)),
+            ..Default::default()
+        };
+        let notes = vec![
+            NoteFormat::default(),
+            with_card(date(2026, 10, 21)),
+            with_card(date(2026, 10, 22)),
+            with_card(date(2026, 10, 1)),
+            NoteFormat::default(),
+        ];
+        assert_eq!(due_notes(&notes, &[0, 1, 2, 3, 4], today), vec![3, 1, 0, 4]);
+        // only the candidates are considered
+        assert_eq!(due_notes(&notes, &[1, 2, 4], today), vec![1, 4]);
+    }
+
+    #[test]
+    fn cards_round_trip_through_meta() {
+        let card = card(2.36, 24, 3, date(2026, 11, 14));
+        assert_eq!(card.to_meta(), "2.36,24,3,2026-11-14");
+        assert_eq!(Card::parse(&card.to_meta()), Some(card));
+        assert_eq!(Card::parse("2.5,1,1"), None);
+        assert_eq!(Card::parse("2.5,1,1,2026-11-14,x"), None);
+    }
+}
@@ -0,0 +1,213 @@
+//! Incremental search in the TUI list, matching the zsh `note find`:
+//! case-insensitive substring search in the title, the body, or both.
+
+use crate::input::TextInput;
+use crate::NoteFormat;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scope {
+    #[default]
+    All,
+    Title,
+    Body,
+}
+
+impl Scope {
+    pub fn next(self) -> Scope {
+        match self {
+            Scope::All => Scope::Title,
+            Scope::Title => Scope::Body,
+            Scope::Body => Scope::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Scope::All => "title+body",
+            Scope::Title => "title",
+            Scope::Body => "body",
+        }
+    }
+}
+
+/// The search bar state. `editing` is true while the query is being typed;
+/// afterwards the query stays active for `n`/`N` and highlighting.
+#[derive(Clone, Debug, Default)]
+pub struct Search {
+    pub query: TextInput,
+    pub scope: Scope,
+    pub editing: bool,
+}
+
+impl Search {
+    /// The query to highlight in the title and body, if any.
+    pub fn highlights(&self) -> (Option<&str>, Option<&str>) {
+        if self.query.is_empty() {
+            return (None, None);
+        }
+        let query = Some(self.query.value());
+        match self.scope {
+            Scope::All => (query, query),
+            Scope::Title => (query, None),
+            Scope::Body => (None, query),
+        }
+    }
+
+    pub fn matches(&self, note: &NoteFormat) -> bool {
+        let (title, body) = self.highlights();
+        title.is_some_and(|query| !find_all(&note.text, query).is_empty())
+            || body.is_some_and(|query| !find_all(&note.body, query).is_empty())
+    }
+
+    /// Position in `view` of the next matching note after `from` (or
+    /// before it going backwards), wrapping around. `inclusive` also
+    /// accepts `from` itself, so typing keeps the current match.
+    pub fn step(
+        &self,
+        notes: &[NoteFormat],
+        view: &[usize],
+        from: usize,
+        forward: bool,
+        inclusive: bool,
+    ) -> Option<usize> {
+        let len = view.len();
+        if len == 0 {
+            return None;
+        }
+        let start = if inclusive { 0 } else { 1 };
+        (start..=len)
+            .map(|offset| {
+                if forward {
+                    (from + offset) % len
+                } else {
+                    (from + len * 2 - offset) % len
+                }
+            })
+            .find(|&pos| self.matches(&notes[view[pos]]))
+    }
+}
+
+/// Byte ranges of the case-insensitive matches of `query` in `text`.
+pub fn find_all(text: &str, query: &str) -> Vec<(usize, usize)> {
+    let mut ranges = vec![];
+    if query.is_empty() {
+        return ranges;
+    }
+    let mut i = 0;
+    while i < text.len() {
+        match match_at(&text[i..], query) {
+            Some(len) => {
+                ranges.push((i, i + len));
+                i += len;
+            }
+            None => i += text[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    ranges
+}
+
+/// Length in bytes of the match of `query` at the start of `text`.
+fn match_at(text: &str, query: &str) -> Option<usize> {
+    let mut chars = text.char_indices();
+    for q in query.chars() {
+        let (_, c) = chars.next()?;
+        if !c.to_lowercase().eq(q.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(chars.next().map_or(text.len(), |(i, _)| i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(text: &str, body: &str) -> NoteFormat {
+        NoteFormat {
+            text: text.to_string(),
+            body: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn search(query: &str, scope: Scope) -> Search {
+        Search {
+            query: TextInput::new(query),
+            scope,
+            editing: false,
+        }
+    }
+
+    #[test]
+    fn matches_ignore_case() {
+        assert_eq!(find_all("Hello hello", "HELLO"), [(0, 5), (6, 11)]);
+        assert_eq!(find_all("say hi", "hi"), [(4, 6)]);
+        assert!(find_all("hello", "bye").is_empty());
+        assert!(find_all("hi", "high").is_empty());
+        assert!(find_all("hello", "").is_empty());
+    }
+
+    #[test]
+    fn matches_do_not_overlap() {
+        assert_eq!(find_all("aaaa", "aa"), [(0, 2), (2, 4)]);
+        assert_eq!(find_all("aaa", "aa"), [(0, 2)]);
+    }
+
+    #[test]
+    fn ranges_are_bytes_on_char_boundaries() {
+        assert_eq!(find_all("café Café", "É"), [(3, 5), (9, 11)]);
+        assert_eq!(find_all("日本語の本", "本"), [(3, 6), (12, 15)]);
+        for (start, end) in find_all("→ a → b", "→") {
+            assert_eq!(&"→ a → b"[start..end], "→");
+        }
+    }
+
+    #[test]
+    fn highlights_follow_the_scope() {
+        let notes = [note("milk", "buy"), note("bread", "and milk")];
+        assert_eq!(
+            search("milk", Scope::All).highlights(),
+            (Some("milk"), Some("milk"))
+        );
+        assert_eq!(
+            search("milk", Scope::Title).highlights(),
+            (Some("milk"), None)
+        );
+        assert_eq!(
+            search("milk", Scope::Body).highlights(),
+            (None, Some("milk"))
+        );
+        assert_eq!(search("", Scope::All).highlights(), (None, None));
+
+        let matching = |scope| {
+            notes
+                .iter()
+                .map(|note| search("MILK", scope).matches(note))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matching(Scope::All), [true, true]);
+        assert_eq!(matching(Scope::Title), [true, false]);
+        assert_eq!(matching(Scope::Body), [false, true]);
+    }
+
+    #[test]
+    fn steps_wrap_around_the_view() {
+        let notes = [
+            note("milk", ""),
+            note("bread", ""),
+            note("more milk", ""),
+            note("eggs", ""),
+        ];
+        let view = [0, 1, 2, 3];
+        let search = search("milk", Scope::All);
+        assert_eq!(search.step(&notes, &view, 0, true, false), Some(2));
+        assert_eq!(search.step(&notes, &view, 2, true, false), Some(0));
+        assert_eq!(search.step(&notes, &view, 0, false, false), Some(2));
+        assert_eq!(search.step(&notes, &view, 3, false, false), Some(2));
+        assert_eq!(search.step(&notes, &view, 2, true, true), Some(2));
+        // the only match is found again from itself
+        assert_eq!(search.step(&notes, &[3, 0], 1, true, false), Some(1));
+        assert_eq!(search.step(&notes, &[1, 3], 0, true, true), None);
+        assert_eq!(search.step(&notes, &[], 0, true, true), None);
+    }
+}
@@ -0,0 +1,87 @@
+//! Note templates such as "Meeting" or "Bug".
+//!
+//! A template is a text file in the templates directory: the first line is
+//! the title, the second the body, and the file name without `.txt` is the
+//! template name. `{{date}}`, `{{time}}`, `{{cwd}}` and `{{user}}` are
+//! replaced when a note is created from it.
+
+use chrono::NaiveDateTime;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug)]
+pub struct Template {
+    pub name: String,
+    pub title: String,
+    pub body: String,
+}
+
+impl Template {
+    /// Title and body with the variables filled in.
+    pub fn expand(&self, now: NaiveDateTime) -> (String, String) {
+        (expand(&self.title, now), expand(&self.body, now))
+    }
+}
+
+/// `NOTE_TEMPLATES_DIR`, or `templates/` next to the notebook.
+pub fn templates_dir(notes_path: &str) -> PathBuf {
+    match std::env::var("NOTE_TEMPLATES_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => Path::new(notes_path)
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("templates"),
+    }
+}
+
+/// Every template in `dir`, sorted by name. A missing directory has none.
+pub fn load_all(dir: &Path) -> Vec<Template> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut templates: Vec<Template> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let text = fs::read_to_string(&path).ok()?;
+            let mut lines = text.lines();
+            Some(Template {
+                name,
+                title: lines
+                    .next()
+                    .unwrap_or("")
+                    .trim_end_matches('\r')
+                    .to_string(),
+                body: lines
+                    .next()
+                    .unwrap_or("")
+                    .trim_end_matches('\r')
+                    .to_string(),
+            })
+        })
+        .collect();
+    templates.sort_by_key(|template| template.name.to_lowercase());
+    templates
+}
+
+/// The template called `name`, ignoring case.
+pub fn find<'a>(templates: &'a [Template], name: &str) -> Option<&'a Template> {
+    templates
+        .iter()
+        .find(|template| template.name.eq_ignore_ascii_case(name))
+}
+
+fn expand(text: &str, now: NaiveDateTime) -> String {
+    let cwd = std::env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_default();
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+    text.replace("{{date}}", &now.format("%Y-%m-%d").to_string())
+        .replace("{{time}}", &now.format("%H:%M").to_string())
+        .replace("{{cwd}}", &cwd)
+        .replace("{{user}}", &user)
+}
@@ -0,0 +1,581 @@
+//! Colors of the TUI.
+//!
+//! `NOTE_THEME` picks one of the built-in themes, `dark` (the default),
+//! `light` and `high-contrast`, or a theme file: a path, or a name such as
+//! `solarized` for `themes/solarized.txt` next to the notes. A theme file
+//! starts from a built-in theme and restyles parts of the screen, one per
+//! line:
+//!
+//! ```text
+//! # comments start with #
+//! base            light
+//! selected        fg=black bg=#ffd75f bold
+//! match           fg=black bg=light-cyan
+//! priority.urgent fg=red bold
+//! label.gray      fg=246
+//! ```
+//!
+//! When `NO_COLOR` is set, colors are dropped from whichever theme is used
+//! and backgrounds, such as the selected row, become reverse video.
+
+use crate::label::Label;
+use crate::priority::Priority;
+use ratatui::style::{Color, Modifier, Style};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// The theme the TUI draws with, dark until `set` is called.
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(Theme::dark)
+}
+
+/// Chooses the theme, once, before the first frame is drawn.
+pub fn set(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+#[derive(Clone, Debug)]
+pub struct Theme {
+    /// The selected row of every list.
+    pub selected: Style,
+    /// The selected row of the color label picker, which must not hide the
+    /// colors it lists.
+    pub selected_label: Style,
+    pub border: Style,
+    /// The border of the panel with the focus.
+    pub focused: Style,
+    /// Inside popups, over the notes.
+    pub popup: Style,
+    pub popup_border: Style,
+    /// Text found by a search.
+    pub search_match: Style,
+    /// Characters matched by the fuzzy finder.
+    pub fuzzy_match: Style,
+    /// Custom field columns and the review grades.
+    pub accent: Style,
+    /// The `[due …]`, `[↻ …]` and `[⏱ …]` tags after a note.
+    pub tag: Style,
+    /// Hints and other secondary text.
+    pub hint: Style,
+    pub done: Style,
+    pub overdue: Style,
+    pub due_today: Style,
+    pub error: Style,
+    pub info: Style,
+    /// The borders of the find and filter bars.
+    pub warning: Style,
+    /// By priority, from none to urgent.
+    priorities: [Style; 5],
+    /// In the order of `Label::ALL`.
+    labels: [Style; 7],
+}
+
+const fn fg(color: Color) -> Style {
+    Style::new().fg(color)
+}
+
+const fn bg(color: Color) -> Style {
+    Style::new().bg(color)
+}
+
+impl Theme {
+    /// The colors the TUI always had, for dark terminals.
+    pub fn dark() -> Theme {
+        Theme {
+            selected: bg(Color::Blue),
+            selected_label: bg(Color::DarkGray),
+            border: Style::new(),
+            focused: fg(Color::Cyan),
+            popup: Style::new(),
+            popup_border: Style::new(),
+            search_match: fg(Color::Black).bg(Color::Yellow),
+            fuzzy_match: fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            accent: fg(Color::Cyan),
+            tag: Style::new(),
+            hint: fg(Color::DarkGray),
+            done: Style::new().add_modifier(Modifier::DIM.union(Modifier::CROSSED_OUT)),
+            overdue: fg(Color::Red),
+            due_today: fg(Color::Yellow),
+            error: fg(Color::Red),
+            info: fg(Color::Green),
+            warning: fg(Color::Yellow),
+            priorities: [
+                Style::new(),
+                fg(Color::Blue),
+                fg(Color::Green),
+                fg(Color::Yellow),
+                fg(Color::Red),
+            ],
+            labels: [
+                fg(Color::Red),
+                fg(Color::Rgb(255, 165, 0)),
+                fg(Color::Yellow),
+                fg(Color::Green),
+                fg(Color::Blue),
+                fg(Color::Magenta),
+                fg(Color::Gray),
+            ],
+        }
+    }
+
+    /// Darker colors that stay readable on a white background, and a pale
+    /// selection the text shows through.
+    pub fn light() -> Theme {
+        let dark_yellow = Color::Indexed(136);
+        let orange = Color::Indexed(166);
+        Theme {
+            selected: fg(Color::Black).bg(Color::Indexed(153)),
+            selected_label: bg(Color::Indexed(252)),
+            border: fg(Color::Indexed(245)),
+            focused: fg(Color::Blue),
+            popup: Style::new(),
+            popup_border: fg(Color::Indexed(240)),
+            search_match: fg(Color::Black).bg(Color::Indexed(229)),
+            fuzzy_match: fg(orange).add_modifier(Modifier::BOLD),
+            accent: fg(Color::Indexed(30)),
+            tag: Style::new(),
+            hint: fg(Color::Indexed(244)),
+            done: Style::new().add_modifier(Modifier::DIM.union(Modifier::CROSSED_OUT)),
+            overdue: fg(Color::Red),
+            due_today: fg(dark_yellow),
+            error: fg(Color::Red),
+            info: fg(Color::Indexed(28)),
+            warning: fg(dark_yellow),
+            priorities: [
+                Style::new(),
+                fg(Color::Blue),
+                fg(Color::Indexed(28)),
+                fg(orange),
+                fg(Color::Red),
+            ],
+            labels: [
+                fg(Color::Red),
+                fg(orange),
+                fg(dark_yellow),
+                fg(Color::Indexed(28)),
+                fg(Color::Blue),
+                fg(Color::Magenta),
+                fg(Color::Indexed(244)),
+            ],
+        }
+    }
+
+    /// Bright colors and bold text on black, with nothing dimmed.
+    pub fn high_contrast() -> Theme {
+        let bold = |color| fg(color).add_modifier(Modifier::BOLD);
+        Theme {
+            selected: fg(Color::Black)
+                .bg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+            selected_label: Style::new().add_modifier(Modifier::BOLD.union(Modifier::UNDERLINED)),
+            border: fg(Color::White),
+            focused: bold(Color::LightYellow),
+            popup: fg(Color::White).bg(Color::Black),
+            popup_border: fg(Color::White),
+            search_match: fg(Color::Black)
+                .bg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+            fuzzy_match: bold(Color::LightYellow).add_modifier(Modifier::UNDERLINED),
+            accent: fg(Color::LightCyan),
+            tag: fg(Color::White),
+            hint: fg(Color::Gray),
+            done: Style::new().add_modifier(Modifier::CROSSED_OUT),
+            overdue: bold(Color::LightRed),
+            due_today: bold(Color::LightYellow),
+            error: bold(Color::LightRed),
+            info: bold(Color::LightGreen),
+            warning: bold(Color::LightYellow),
+            priorities: [
+                Style::new(),
+                bold(Color::LightBlue),
+                bold(Color::LightGreen),
+                bold(Color::LightYellow),
+                bold(Color::LightRed),
+            ],
+            labels: [
+                fg(Color::LightRed),
+                fg(Color::Rgb(255, 165, 0)),
+                fg(Color::LightYellow),
+                fg(Color::LightGreen),
+                fg(Color::LightBlue),
+                fg(Color::LightMagenta),
+                fg(Color::Gray),
+            ],
+        }
+    }
+
+    fn built_in(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    pub fn priority(&self, priority: Priority) -> Style {
+        self.priorities[priority as usize]
+    }
+
+    pub fn label(&self, label: Label) -> Style {
+        let i = Label::ALL.iter().position(|&l| l == label).unwrap_or(0);
+        self.labels[i]
+    }
+
+    /// The theme named by `NOTE_THEME`, with the colors taken out when
+    /// `NO_COLOR` is set, or every problem found in its file.
+    pub fn load(notes_path: &str) -> Result<Theme, Vec<String>> {
+        let name = std::env::var("NOTE_THEME").unwrap_or_default();
+        let theme = match Theme::built_in(&name) {
+            Some(theme) => theme,
+            None if name.is_empty() => Theme::dark(),
+            None => {
+                let path = theme_path(&name, notes_path);
+                let text = fs::read_to_string(&path).map_err(|err| {
+                    vec![format!(
+                        "Theme \"{}\" is not dark, light, high-contrast or a readable file ({}: {})",
+                        name,
+                        path.display(),
+                        err
+                    )]
+                })?;
+                Theme::parse(&text).map_err(|errors| {
+                    errors
+                        .into_iter()
+                        .map(|(line, err)| format!("{}:{}: {}", path.display(), line, err))
+                        .collect::<Vec<_>>()
+                })?
+            }
+        };
+        // any value but an empty one, as https://no-color.org asks
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Ok(if no_color {
+            theme.without_color()
+        } else {
+            theme
+        })
+    }
+
+    /// A theme file: a built-in theme, given by a `base` line or dark, with
+    /// the styles of the other lines. Errors come with their line number.
+    fn parse(text: &str) -> Result<Theme, Vec<(usize, String)>> {
+        let mut theme = Theme::dark();
+        let mut errors = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let n = n + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            if name == "base" {
+                match Theme::built_in(value.trim()) {
+                    // before the other lines, so they restyle the base
+                    Some(base) if n == first_line(text) => theme = base,
+                    Some(_) => errors.push((n, "\"base\" must be the first line".into())),
+                    None => errors.push((
+                        n,
+                        format!(
+                            "Unknown base \"{}\" (dark, light or high-contrast)",
+                            value.trim()
+                        ),
+                    )),
+                }
+                continue;
+            }
+            let Some(style) = theme.part_mut(name) else {
+                errors.push((n, format!("Unknown part \"{}\"", name)));
+                continue;
+            };
+            match parse_style(value) {
+                Ok(parsed) => *style = parsed,
+                Err(err) => errors.push((n, err)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(theme)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The style of the part of the screen called `name` in theme files.
+    fn part_mut(&mut self, name: &str) -> Option<&mut Style> {
+        if let Some(level) = name.strip_prefix("priority.") {
+            let priority = Priority::parse(level).filter(|&p| p != Priority::None)?;
+            return Some(&mut self.priorities[priority as usize]);
+        }
+        if let Some(color) = name.strip_prefix("label.") {
+            let i = Label::ALL.iter().position(|l| l.to_string() == color)?;
+            return Some(&mut self.labels[i]);
+        }
+        Some(match name {
+            "selected" => &mut self.selected,
+            "selected-label" => &mut self.selected_label,
+            "border" => &mut self.border,
+            "focused" => &mut self.focused,
+            "popup" => &mut self.popup,
+            "popup-border" => &mut self.popup_border,
+            "match" => &mut self.search_match,
+            "fuzzy-match" => &mut self.fuzzy_match,
+            "accent" => &mut self.accent,
+            "tag" => &mut self.tag,
+            "hint" => &mut self.hint,
+            "done" => &mut self.done,
+            "overdue" => &mut self.overdue,
+            "due-today" => &mut self.due_today,
+            "error" => &mut self.error,
+            "info" => &mut self.info,
+            "warning" => &mut self.warning,
+            _ => return None,
+        })
+    }
+
+    /// The same theme without colors. What stood out by its background
+    /// is shown in reverse video instead, so the selection stays visible.
+    fn without_color(mut self) -> Theme {
+        let strip = |style: &mut Style| {
+            if style.bg.is_some_and(|bg| bg != Color::Reset) {
+                *style = style.add_modifier(Modifier::REVERSED);
+            }
+            style.fg = None;
+            style.bg = None;
+        };
+        for style in [
+            &mut self.selected,
+            &mut self.selected_label,
+            &mut self.border,
+            &mut self.focused,
+            &mut self.popup,
+            &mut self.popup_border,
+            &mut self.search_match,
+            &mut self.fuzzy_match,
+            &mut self.accent,
+            &mut self.tag,
+            &mut self.hint,
+            &mut self.done,
+            &mut self.overdue,
+            &mut self.due_today,
+            &mut self.error,
+            &mut self.info,
+            &mut self.warning,
+        ] {
+            strip(style);
+        }
+        self.priorities.iter_mut().for_each(strip);
+        self.labels.iter_mut().for_each(strip);
+        self
+    }
+}
+
+/// A theme given by name is `themes/<name>.txt` next to the notes; one
+/// with a `/` or an extension is a path.
+fn theme_path(name: &str, notes_path: &str) -> PathBuf {
+    let path = Path::new(name);
+    if name.contains('/') || path.extension().is_some() {
+        return path.to_path_buf();
+    }
+    Path::new(notes_path)
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("themes")
+        .join(format!("{}.txt", name))
+}
+
+/// The number of the first line that isn't blank or a comment.
+fn first_line(text: &str) -> usize {
+    text.lines()
+        .position(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map_or(0, |i| i + 1)
+}
+
+/// A style such as `fg=black bg=#ffd75f bold`. Colors are names (`red`,
+/// `light-blue`, `dark-gray`, `reset`), 256-color numbers or `#rrggbb`;
+/// `none` alone is the plain text of the terminal.
+fn parse_style(value: &str) -> Result<Style, String> {
+    let mut style = Style::new();
+    for word in value.split_whitespace() {
+        if let Some((key, color)) = word.split_once('=') {
+            let color = Color::from_str(color)
+                .map_err(|_| format!("Unknown color \"{}\" in \"{}\"", color, word))?;
+            style = match key {
+                "fg" => style.fg(color),
+                "bg" => style.bg(color),
+                _ => return Err(format!("Expected fg= or bg=, not \"{}\"", word)),
+            };
+            continue;
+        }
+        let modifier = match word {
+            "none" => continue,
+            "bold" => Modifier::BOLD,
+            "dim" => Modifier::DIM,
+            "italic" => Modifier::ITALIC,
+            "underlined" => Modifier::UNDERLINED,
+            "reversed" => Modifier::REVERSED,
+            "crossed-out" => Modifier::CROSSED_OUT,
+            _ => {
+                return Err(format!(
+                    "Unknown style \"{}\" (fg=, bg=, bold, dim, italic, underlined, reversed, crossed-out or none)",
+                    word
+                ))
+            }
+        };
+        style = style.add_modifier(modifier);
+    }
+    Ok(style)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(text: &str) -> Vec<(usize, String)> {
+        Theme::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn styles_read_names_numbers_and_hex() {
+        assert_eq!(
+            parse_style("fg=black bg=#ffd75f bold"),
+            Ok(fg(Color::Black)
+                .bg(Color::Rgb(0xff, 0xd7, 0x5f))
+                .add_modifier(Modifier::BOLD))
+        );
+        assert_eq!(
+            parse_style("fg=light-cyan bg=dark-gray"),
+            Ok(fg(Color::LightCyan).bg(Color::DarkGray))
+        );
+        assert_eq!(parse_style("fg=246"), Ok(fg(Color::Indexed(246))));
+        assert_eq!(parse_style("bg=reset"), Ok(bg(Color::Reset)));
+        assert_eq!(
+            parse_style("dim italic underlined reversed crossed-out"),
+            Ok(Style::new().add_modifier(
+                Modifier::DIM
+                    | Modifier::ITALIC
+                    | Modifier::UNDERLINED
+                    | Modifier::REVERSED
+                    | Modifier::CROSSED_OUT
+            ))
+        );
+        assert_eq!(parse_style("none"), Ok(Style::new()));
+        assert_eq!(parse_style(""), Ok(Style::new()));
+    }
+
+    #[test]
+    fn styles_reject_unknown_words() {
+        assert_eq!(
+            parse_style("fg=purplish"),
+            Err("Unknown color \"purplish\" in \"fg=purplish\"".into())
+        );
+        assert_eq!(
+            parse_style("ul=red"),
+            Err("Expected fg= or bg=, not \"ul=red\"".into())
+        );
+        assert!(parse_style("blink")
+            .unwrap_err()
+            .starts_with("Unknown style \"blink\""));
+    }
+
+    #[test]
+    fn files_restyle_parts_of_their_base() {
+        let theme = Theme::parse(
+            "# a comment\n\
+             \n\
+             base light\n\
+             selected        fg=black bg=#ffd75f bold\n\
+             match           fg=black bg=light-cyan\n\
+             priority.urgent fg=red bold\n\
+             label.gray      fg=246\n",
+        )
+        .unwrap();
+        assert_eq!(
+            theme.selected,
+            fg(Color::Black)
+                .bg(Color::Rgb(0xff, 0xd7, 0x5f))
+                .add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(theme.search_match, fg(Color::Black).bg(Color::LightCyan));
+        assert_eq!(
+            theme.priority(Priority::Urgent),
+            fg(Color::Red).add_modifier(Modifier::BOLD)
+        );
+        assert_eq!(theme.label(Label::Gray), fg(Color::Indexed(246)));
+        // the rest comes from the base
+        assert_eq!(theme.border, Theme::light().border);
+        assert_eq!(theme.label(Label::Red), Theme::light().label(Label::Red));
+
+        let theme = Theme::parse("hint fg=white").unwrap();
+        assert_eq!(theme.hint, fg(Color::White));
+        assert_eq!(theme.selected, Theme::dark().selected);
+    }
+
+    #[test]
+    fn every_bad_line_is_reported_with_its_number() {
+        assert_eq!(
+            errors(
+                "base solarized\n\
+                 selected fg=black\n\
+                 # fine\n\
+                 sidebar fg=red\n\
+                 priority.none bold\n\
+                 label.teal fg=cyan\n\
+                 hint fg=nope\n\
+                 base dark\n"
+            ),
+            vec![
+                (
+                    1,
+                    "Unknown base \"solarized\" (dark, light or high-contrast)".into()
+                ),
+                (4, "Unknown part \"sidebar\"".into()),
+                (5, "Unknown part \"priority.none\"".into()),
+                (6, "Unknown part \"label.teal\"".into()),
+                (7, "Unknown color \"nope\" in \"fg=nope\"".into()),
+                (8, "\"base\" must be the first line".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_color_keeps_only_modifiers() {
+        let theme = Theme::high_contrast().without_color();
+        assert_eq!(
+            theme.selected,
+            Style::new().add_modifier(Modifier::BOLD | Modifier::REVERSED)
+        );
+        assert_eq!(theme.overdue, Style::new().add_modifier(Modifier::BOLD));
+        assert_eq!(theme.border, Style::new());
+        assert_eq!(theme.popup, Style::new().add_modifier(Modifier::REVERSED));
+
+        let theme = Theme::dark().without_color();
+        assert_eq!(
+            theme.selected,
+            Style::new().add_modifier(Modifier::REVERSED)
+        );
+        assert_eq!(theme.hint, Style::new());
+        assert_eq!(
+            theme.done,
+            Style::new().add_modifier(Modifier::DIM | Modifier::CROSSED_OUT)
+        );
+        for priority in [Priority::Low, Priority::Urgent] {
+            assert_eq!(theme.priority(priority), Style::new());
+        }
+        for &label in Label::ALL.iter() {
+            assert_eq!(theme.label(label), Style::new());
+        }
+    }
+
+    #[test]
+    fn reset_backgrounds_are_not_reversed() {
+        let mut theme = Theme::dark();
+        theme.selected = bg(Color::Reset);
+        assert_eq!(theme.without_color().selected, Style::new());
+    }
+}
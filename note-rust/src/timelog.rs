@@ -0,0 +1,95 @@
+//! Time tracking: timer sessions recorded on notes.
+//!
+//! A note keeps its finished sessions and, while its timer runs, the time
+//! it was started. Both are stored in `note.meta` with second precision.
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+
+use crate::dateparse;
+use crate::NoteFormat;
+
+/// One stretch of time spent on a note.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl Session {
+    pub fn duration(self) -> Duration {
+        self.end - self.start
+    }
+
+    /// `start/end`, as stored in `note.meta`.
+    pub fn to_meta(self) -> String {
+        format!(
+            "{}/{}",
+            format_datetime(self.start),
+            format_datetime(self.end)
+        )
+    }
+
+    pub fn parse(s: &str) -> Option<Session> {
+        let (start, end) = s.split_once('/')?;
+        Some(Session {
+            start: parse_datetime(start)?,
+            end: parse_datetime(end)?,
+        })
+    }
+}
+
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+pub fn format_datetime(dt: NaiveDateTime) -> String {
+    dt.format(DATETIME_FORMAT).to_string()
+}
+
+pub fn parse_datetime(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, DATETIME_FORMAT).ok()
+}
+
+/// Time spent on `note` in sessions started on or after `since`, counting
+/// a running timer up to `now`.
+pub fn total(note: &NoteFormat, since: Option<NaiveDateTime>, now: NaiveDateTime) -> Duration {
+    let running = note.timer.map(|start| Session { start, end: now });
+    note.sessions
+        .iter()
+        .copied()
+        .chain(running)
+        .filter(|session| since.is_none_or(|since| session.start >= since))
+        .map(Session::duration)
+        .fold(Duration::zero(), |sum, d| sum + d)
+}
+
+/// `1h 05m`, or `42m` under an hour.
+pub fn format_duration(d: Duration) -> String {
+    let minutes = d.num_minutes();
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// `01:02:03`, for the running timer.
+pub fn format_clock(d: Duration) -> String {
+    let seconds = d.num_seconds().max(0);
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Start of the day a `--since` argument names. Anything `dateparse`
+/// accepts works; a bare weekday such as `monday` means the most recent
+/// one rather than the next.
+pub fn parse_since(s: &str, now: NaiveDateTime) -> Option<NaiveDate> {
+    let date = dateparse::parse(s, now)?.date;
+    if date > now.date() {
+        Some(date - Duration::days(7))
+    } else {
+        Some(date)
+    }
+}
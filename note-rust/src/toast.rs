@@ -0,0 +1,76 @@
+//! Short messages shown in the corner of the TUI: confirmations such as
+//! "Added note 12", and errors such as an invalid date or a failed save.
+//! A message disappears after a few seconds, errors a little later than
+//! confirmations, or when the next one replaces it.
+
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+impl Level {
+    fn lifetime(self) -> Duration {
+        match self {
+            Level::Info => Duration::from_secs(3),
+            Level::Error => Duration::from_secs(6),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Toast {
+    pub text: String,
+    pub level: Level,
+    shown: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct Toasts {
+    current: Option<Toast>,
+}
+
+impl Toasts {
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.show(text.into(), Level::Info);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.show(text.into(), Level::Error);
+    }
+
+    /// Shows the error of `result`, if any, after `context` such as
+    /// "Could not save". Returns the value otherwise.
+    pub fn report<T, E: Display>(&mut self, result: Result<T, E>, context: &str) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.error(format!("{}: {}", context, err));
+                None
+            }
+        }
+    }
+
+    /// The message to show now, forgetting it once it has expired.
+    pub fn current(&mut self, now: Instant) -> Option<&Toast> {
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|toast| now.duration_since(toast.shown) >= toast.level.lifetime())
+        {
+            self.current = None;
+        }
+        self.current.as_ref()
+    }
+
+    fn show(&mut self, text: String, level: Level) {
+        self.current = Some(Toast {
+            text,
+            level,
+            shown: Instant::now(),
+        });
+    }
+}
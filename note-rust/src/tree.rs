@@ -0,0 +1,76 @@
+//! Folder hierarchy shown to the left of the note list.
+//!
+//! Folders aren't stored on their own. A note's `folder` is a `/` separated
+//! path such as `work/infra`, and the tree is built from the paths in use, so
+//! a folder goes away once its last note is moved out. The empty path is the
+//! top level.
+
+use std::collections::{BTreeSet, HashSet};
+
+/// Trims whitespace and stray slashes: ` /work//infra/ ` becomes `work/infra`.
+pub fn normalize(path: &str) -> String {
+    path.split('/')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether a note in `note_folder` belongs in `folder` or one of its
+/// subfolders.
+pub fn contains(folder: &str, note_folder: &str) -> bool {
+    folder.is_empty()
+        || note_folder == folder
+        || note_folder
+            .strip_prefix(folder)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+pub struct FolderRow {
+    pub path: String,
+    pub name: String,
+    pub depth: usize,
+    pub has_children: bool,
+    /// Notes in this folder and its subfolders.
+    pub count: usize,
+}
+
+/// Rows of the tree, starting with the top level, skipping the children of
+/// `collapsed` folders. `folders` holds the folder of every note shown.
+pub fn rows(folders: &[&str], collapsed: &HashSet<String>) -> Vec<FolderRow> {
+    let mut paths: BTreeSet<Vec<&str>> = BTreeSet::new();
+    for folder in folders {
+        let parts: Vec<&str> = folder.split('/').filter(|p| !p.is_empty()).collect();
+        for end in 1..=parts.len() {
+            paths.insert(parts[..end].to_vec());
+        }
+    }
+
+    let count = |path: &str| folders.iter().filter(|f| contains(path, f)).count();
+    let mut rows = vec![FolderRow {
+        path: String::new(),
+        name: "All notes".to_string(),
+        depth: 0,
+        has_children: !paths.is_empty(),
+        count: folders.len(),
+    }];
+    let paths: Vec<Vec<&str>> = paths.into_iter().collect();
+    for (i, parts) in paths.iter().enumerate() {
+        let hidden = (1..parts.len()).any(|end| collapsed.contains(&parts[..end].join("/")));
+        if hidden {
+            continue;
+        }
+        let path = parts.join("/");
+        let has_children = paths
+            .get(i + 1)
+            .is_some_and(|next| next.len() > parts.len() && next.starts_with(parts));
+        rows.push(FolderRow {
+            count: count(&path),
+            name: parts[parts.len() - 1].to_string(),
+            depth: parts.len(),
+            has_children,
+            path,
+        });
+    }
+    rows
+}
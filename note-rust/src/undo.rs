@@ -0,0 +1,234 @@
+//! Undo and redo for the changes made to notes in the TUI.
+//!
+//! Changes are undone in the reverse order they were made, so the indices
+//! they record are still right when they are reverted. Every change is
+//! recorded, metadata included, which keeps the notes a change saved the
+//! same as the ones it finds when it is undone. The history lasts for the
+//! whole session, across saves; changes are recorded once they are written
+//! and the caller writes the notebook after each undo or redo.
+
+use crate::links;
+use crate::NoteFormat;
+
+#[derive(Clone)]
+pub enum Change {
+    Added {
+        index: usize,
+        note: NoteFormat,
+    },
+    /// Notes that changed: index, before and after. Most changes touch one
+    /// note; updating links after a rename or switching timers several.
+    Edited(Vec<(usize, NoteFormat, NoteFormat)>),
+    /// A deleted note, with the bodies whose numeric links were renumbered
+    /// because of it: index after the deletion, before and after.
+    Deleted {
+        index: usize,
+        note: NoteFormat,
+        relinked: Vec<(usize, String, String)>,
+    },
+}
+
+impl Change {
+    /// Deletes note `index` and renumbers the links to the notes after it,
+    /// returning the change and the notes whose links to it were dropped
+    /// because its title didn't fit.
+    pub fn delete(notes: &mut Vec<NoteFormat>, index: usize) -> (Change, Vec<usize>) {
+        let (relinked, dropped) = links::after_removal(notes, index);
+        let note = notes.remove(index);
+        let relinked = relinked
+            .into_iter()
+            .map(|(i, body)| {
+                let before = std::mem::replace(&mut notes[i].body, body.clone());
+                (i, before, body)
+            })
+            .collect();
+        let change = Change::Deleted {
+            index,
+            note,
+            relinked,
+        };
+        (change, dropped)
+    }
+
+    /// The note the change is about, to select after undoing or redoing it.
+    pub fn index(&self) -> usize {
+        match self {
+            Change::Added { index, .. } | Change::Deleted { index, .. } => *index,
+            Change::Edited(edits) => edits.first().map_or(0, |(index, _, _)| *index),
+        }
+    }
+
+    /// Whether undoing or redoing it adds or removes a note, which moves
+    /// the notes after it.
+    pub fn renumbers(&self) -> bool {
+        !matches!(self, Change::Edited(_))
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Change::Added { index, .. } => format!("add note {}", index + 1),
+            Change::Edited(edits) if edits.len() == 1 => {
+                format!("change to note {}", edits[0].0 + 1)
+            }
+            Change::Edited(edits) => format!("change to {} notes", edits.len()),
+            Change::Deleted { index, .. } => format!("delete note {}", index + 1),
+        }
+    }
+
+    /// Makes the change again on the notes it was undone on.
+    pub fn apply(&self, notes: &mut Vec<NoteFormat>) {
+        match self {
+            Change::Added { index, note } => notes.insert(*index, note.clone()),
+            Change::Edited(edits) => {
+                for (index, _, after) in edits {
+                    notes[*index] = after.clone();
+                }
+            }
+            Change::Deleted {
+                index, relinked, ..
+            } => {
+                notes.remove(*index);
+                for (i, _, after) in relinked {
+                    notes[*i].body = after.clone();
+                }
+            }
+        }
+    }
+
+    /// Undoes the change on the notes it was made on.
+    pub fn revert(&self, notes: &mut Vec<NoteFormat>) {
+        match self {
+            Change::Added { index, .. } => {
+                notes.remove(*index);
+            }
+            Change::Edited(edits) => {
+                for (index, before, _) in edits.iter().rev() {
+                    notes[*index] = before.clone();
+                }
+            }
+            Change::Deleted {
+                index,
+                note,
+                relinked,
+            } => {
+                for (i, before, _) in relinked {
+                    notes[*i].body = before.clone();
+                }
+                notes.insert(*index, note.clone());
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct UndoHistory {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+impl UndoHistory {
+    /// Remembers a change that was just made. Anything undone before it
+    /// can no longer be redone.
+    pub fn record(&mut self, change: Change) {
+        self.undo.push(change);
+        self.redo.clear();
+    }
+
+    /// Reverts the last change on `notes`, returning it.
+    pub fn undo(&mut self, notes: &mut Vec<NoteFormat>) -> Option<&Change> {
+        let change = self.undo.pop()?;
+        change.revert(notes);
+        self.redo.push(change);
+        self.redo.last()
+    }
+
+    /// Makes the last undone change again, returning it.
+    pub fn redo(&mut self, notes: &mut Vec<NoteFormat>) -> Option<&Change> {
+        let change = self.redo.pop()?;
+        change.apply(notes);
+        self.undo.push(change);
+        self.undo.last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(text: &str, body: &str) -> NoteFormat {
+        NoteFormat {
+            text: text.to_string(),
+            body: body.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn edits_restore_the_whole_note() {
+        let mut notes = vec![note("a", "body")];
+        let mut after = notes[0].clone();
+        after.body = "new body".to_string();
+        after.pinned = true;
+        after.folder = "work".to_string();
+        let mut history = UndoHistory::default();
+        notes[0] = after.clone();
+        history.record(Change::Edited(vec![(0, note("a", "body"), after.clone())]));
+
+        history.undo(&mut notes);
+        assert_eq!(notes[0], note("a", "body"));
+        history.redo(&mut notes);
+        assert_eq!(notes[0], after);
+    }
+
+    #[test]
+    fn redo_brings_back_the_note_as_it_was_last() {
+        let mut notes = vec![note("a", "")];
+        let mut history = UndoHistory::default();
+        notes.push(note("b", ""));
+        history.record(Change::Added {
+            index: 1,
+            note: notes[1].clone(),
+        });
+        let mut pinned = notes[1].clone();
+        pinned.pinned = true;
+        history.record(Change::Edited(vec![(1, notes[1].clone(), pinned.clone())]));
+        notes[1] = pinned.clone();
+
+        history.undo(&mut notes);
+        history.undo(&mut notes);
+        assert_eq!(notes, vec![note("a", "")]);
+        history.redo(&mut notes);
+        history.redo(&mut notes);
+        assert_eq!(notes[1], pinned);
+        assert!(history.redo(&mut notes).is_none());
+    }
+
+    #[test]
+    fn deletes_renumber_links_and_undo_restores_them() {
+        let original = vec![note("a", "[[2]] [[3]]"), note("b", ""), note("c", "[[1]]")];
+        let mut notes = original.clone();
+        let mut history = UndoHistory::default();
+        history.record(Change::delete(&mut notes, 1).0);
+        assert_eq!(notes, vec![note("a", "[[b]] [[2]]"), note("c", "[[1]]")]);
+
+        history.undo(&mut notes);
+        assert_eq!(notes, original);
+        history.redo(&mut notes);
+        assert_eq!(notes, vec![note("a", "[[b]] [[2]]"), note("c", "[[1]]")]);
+    }
+
+    #[test]
+    fn a_new_change_drops_what_was_undone() {
+        let mut notes = vec![note("a", "")];
+        let mut history = UndoHistory::default();
+        history.record(Change::Edited(vec![(0, note("a", ""), note("b", ""))]));
+        notes[0] = note("b", "");
+        history.undo(&mut notes);
+        history.record(Change::Edited(vec![(0, note("a", ""), note("c", ""))]));
+        notes[0] = note("c", "");
+        assert!(history.redo(&mut notes).is_none());
+        history.undo(&mut notes);
+        assert_eq!(notes[0], note("a", ""));
+        assert!(history.undo(&mut notes).is_none());
+    }
+}
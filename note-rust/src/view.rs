@@ -0,0 +1,130 @@
+//! Which notes the main list shows, and in what order.
+//!
+//! The list works on positions into `notes`, so sorting never reorders
+//! `note.txt` and a note keeps its number whatever the order. The TUI
+//! numbers notes from 1, the zsh script and `note-rust` commands from 0.
+
+use chrono::Local;
+
+use crate::fields::FieldFilter;
+use crate::filter::Filter;
+use crate::label::Label;
+use crate::{tree, NoteFormat};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortMode {
+    /// File order.
+    #[default]
+    Manual,
+    /// Highest priority first.
+    Priority,
+    /// Earliest due date first, notes without one last.
+    Due,
+}
+
+impl SortMode {
+    pub fn next(self) -> SortMode {
+        match self {
+            SortMode::Manual => SortMode::Priority,
+            SortMode::Priority => SortMode::Due,
+            SortMode::Due => SortMode::Manual,
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<SortMode> {
+        match s {
+            "manual" | "none" => Some(SortMode::Manual),
+            "priority" | "pri" => Some(SortMode::Priority),
+            "due" => Some(SortMode::Due),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Manual => "manual",
+            SortMode::Priority => "priority",
+            SortMode::Due => "due date",
+        }
+    }
+}
+
+/// The main list shows either the active notes or the archive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Panel {
+    #[default]
+    Notes,
+    Archive,
+}
+
+impl Panel {
+    pub fn title(self) -> &'static str {
+        match self {
+            Panel::Notes => "[1]: Notes",
+            Panel::Archive => "[2]: Archive",
+        }
+    }
+}
+
+/// What the main list is showing. The TUI keeps one of these and
+/// `note list` builds one from its arguments.
+#[derive(Clone, Debug, Default)]
+pub struct ViewOptions {
+    pub sort: SortMode,
+    pub panel: Panel,
+    /// Only notes in this folder or below it; `None` shows every folder.
+    /// Pinned notes are shown regardless.
+    pub folder: Option<String>,
+    /// Only notes whose custom fields match. Pinned notes are shown regardless.
+    pub field: Option<FieldFilter>,
+    /// Only notes with this color label. Pinned notes are shown regardless.
+    pub label: Option<Label>,
+    /// Only notes matching this expression. Pinned notes are shown regardless.
+    pub filter: Option<Filter>,
+}
+
+/// Indices into `notes` in display order: the notes belonging to the panel,
+/// folder, field filter, filter expression and label, pinned ones first,
+/// each part sorted. Ties keep file order.
+pub fn visible(notes: &[NoteFormat], options: &ViewOptions) -> Vec<usize> {
+    let archived = options.panel == Panel::Archive;
+    let now = Local::now().naive_local();
+    let mut view: Vec<usize> = (0..notes.len())
+        .filter(|&i| notes[i].archived == archived)
+        .filter(|&i| {
+            let note = &notes[i];
+            match &options.folder {
+                Some(folder) => note.pinned || tree::contains(folder, &note.folder),
+                None => true,
+            }
+        })
+        .filter(|&i| {
+            let note = &notes[i];
+            match &options.field {
+                Some(filter) => note.pinned || filter.matches(&note.fields),
+                None => true,
+            }
+        })
+        .filter(|&i| {
+            let note = &notes[i];
+            match &options.filter {
+                Some(filter) => note.pinned || filter.matches(note, now),
+                None => true,
+            }
+        })
+        .filter(|&i| {
+            let note = &notes[i];
+            match options.label {
+                Some(label) => note.pinned || note.label == Some(label),
+                None => true,
+            }
+        })
+        .collect();
+    match options.sort {
+        SortMode::Manual => {}
+        SortMode::Priority => view.sort_by_key(|&i| std::cmp::Reverse(notes[i].priority)),
+        SortMode::Due => view.sort_by_key(|&i| (notes[i].due.is_none(), notes[i].due)),
+    }
+    view.sort_by_key(|&i| !notes[i].pinned);
+    view
+}